@@ -1,42 +1,128 @@
-use crate::download::{handle_download_requests, ModDetails, ModFileDetails};
+use crate::archive::{
+    find_mod_units, install_units, planned_files, preview, read_manifest, same_unique_id,
+    ArchivePreview, Manifest, ModUnit,
+};
+use crate::bisect::{Bisect, Check, ModKey};
+use crate::diagnostics;
+use crate::disable::{self, DisableStrategy, DISABLED_FOLDER};
+use crate::download::{
+    handle_download_requests, DownloadSettings, Downloader, Metadata, MetadataFetcher,
+    SharedSettings, UpdateCheck,
+};
+use crate::error::{Context, Error};
+use crate::game::{self, compare_versions, Versions, GAME_DLL};
+use crate::launch::{self, GameProcess, LaunchMethod};
+use crate::lockfile::{self, Drift, Locked, Lockfile, LOCKFILE_VERSION};
+use crate::logging::{self, info, warning};
+use crate::modlist::{self, Difference, Entry as ListEntry};
+use crate::notify::{Confirmation, Notifications, Notifier};
+use crate::pack::{self, Extracted, PACK_EXTENSION};
+use crate::receipt::{self, InstalledFile};
+use crate::smapi::{self, SMAPI_MOD_ID};
+use crate::smapi_log::{latest_log_path, LogLevel, ProblemKind, SmapiLog};
+use crate::state::{self, Settings, State, STATE_VERSION};
+use crate::store::{partial_path, ArchiveStore, PARTIAL_FOLDER};
 use core::panic;
 use directories_next::ProjectDirs;
-use eframe::{egui, CreationContext, Storage};
+use eframe::egui;
 use egui_extras::{Size, TableBuilder};
-use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::{hash_map::Entry, HashMap};
-use std::fs::{create_dir, create_dir_all, read_dir, remove_dir_all, remove_file, File};
-use std::io::{self, Read, Write};
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+use std::fs::{create_dir_all, read_dir, remove_dir_all, remove_file, File};
+use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{sync_channel, Receiver};
-
-#[cfg(target_os = "linux")]
-use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const HIGH: u64 = u64::MAX - 10000;
 
-#[derive(Serialize, Deserialize, Default, Clone)]
-struct DepGameMod {
-    name: String,
-    zip_name: String,
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct GameMod {
+    pub(crate) name: String,
+    pub(crate) zip_name: String,
     folder_name: String,
-    version: String,
+    pub(crate) version: String,
     author: String,
     link: String,
-    id: u64,
+    pub(crate) mod_id: u64,
+    pub(crate) file_id: u64,
+    /// Every folder the mod created in the Mods directory
+    #[serde(default)]
+    folders: Vec<String>,
+    /// Every file the install wrote, relative to the Mods directory
+    #[serde(default)]
+    pub(crate) installed: Vec<InstalledFile>,
+    /// Archive paths of the units picked for install, empty until a choice was needed
+    #[serde(default)]
+    pub(crate) units: Vec<String>,
+    /// UniqueIDs from the manifests of the installed folders
+    #[serde(default)]
+    pub(crate) unique_ids: Vec<String>,
+    /// Found in the Mods folder without SDMM having an archive for it
+    #[serde(default)]
+    pub(crate) external: bool,
+    /// The mod is tracked as active but none of its folders exist anymore
+    #[serde(skip)]
+    missing: bool,
+    /// The mod's folders were moved into the disabled folder instead of being deleted
+    #[serde(default)]
+    stashed: bool,
+    /// Hash of the archive in the archive store
+    #[serde(default)]
+    pub(crate) hash: String,
+    /// MinimumApiVersion from the mod's manifests when the installed SMAPI is older
+    #[serde(skip)]
+    needs_api: Option<String>,
+    /// Newest version on NexusMods as of the last update check
+    #[serde(default)]
+    latest_version: Option<String>,
+    /// Details are still being fetched from NexusMods, worked out again on startup
+    #[serde(skip)]
+    pending: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct GameMod {
-    name: String,
-    zip_name: String,
-    folder_name: String,
-    version: String,
-    author: String,
-    link: String,
-    mod_id: u64,
-    file_id: u64,
+impl GameMod {
+    /// The same archive, without anything that depends on where it was installed
+    fn library_copy(&self) -> GameMod {
+        GameMod {
+            folders: vec![],
+            installed: vec![],
+            unique_ids: vec![],
+            missing: false,
+            stashed: false,
+            needs_api: None,
+            ..self.clone()
+        }
+    }
+
+    /// Mods installed before every folder was recorded only know their first one
+    fn folder_list(&self) -> Vec<String> {
+        if !self.folders.is_empty() {
+            self.folders.clone()
+        } else if !self.folder_name.is_empty() {
+            vec![self.folder_name.clone()]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Still described by what was known when its download finished, as its details never
+    /// arrived
+    fn placeholder(&self) -> bool {
+        let defaults = GameMod::default();
+        self.name == self.zip_name
+            && self.version == defaults.version
+            && self.author == defaults.author
+    }
+
+    /// Folder name used for archives that have loose files at their root
+    fn fallback_name(&self) -> String {
+        Path::new(&self.zip_name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.name.clone())
+    }
 }
 
 impl Default for GameMod {
@@ -49,23 +135,107 @@ impl Default for GameMod {
             author: "Unknown".into(),
             link: Default::default(),
             mod_id: Default::default(),
-            file_id: Default::default()
+            file_id: Default::default(),
+            folders: Default::default(),
+            installed: Default::default(),
+            units: Default::default(),
+            unique_ids: Default::default(),
+            external: Default::default(),
+            missing: Default::default(),
+            stashed: Default::default(),
+            hash: Default::default(),
+            needs_api: Default::default(),
+            latest_version: Default::default(),
+            pending: Default::default(),
         }
     }
 }
 
+/// A game folder with its own set of installed mods, the archives are shared between all of them
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Installation {
+    pub(crate) name: String,
+    pub(crate) game_path: PathBuf,
+    pub(crate) active: Vec<GameMod>,
+    pub(crate) inactive: Vec<GameMod>,
+}
+
+/// Differences between the active mods and a lockfile
+struct LockReport {
+    lockfile: Lockfile,
+    drift: Vec<Drift>,
+}
+
+/// Another player's mod list next to the active mods
+struct Comparison {
+    /// File the list was loaded from
+    name: String,
+    theirs: Vec<ListEntry>,
+    differences: Vec<Difference>,
+}
+
+/// One-click fix for a difference to another player's mods
+enum Reconcile {
+    /// Enables the matching library mod, disabling the other version first
+    Enable {
+        entry: ListEntry,
+        replaces: Option<GameMod>,
+    },
+    Download(String),
+    OpenPage(String),
+    Disable(GameMod),
+}
+
+/// Outcome of importing a mod list, one status per entry
+struct ImportReport {
+    entries: Vec<(ListEntry, ImportStatus)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ImportStatus {
+    Active,
+    /// In the library but not enabled
+    Found,
+    Downloading,
+    /// Free accounts have to download from the website
+    Manual,
+    Unmatched,
+}
+
+struct InstallChoice {
+    r#mod: GameMod,
+    units: Vec<(ModUnit, Option<Manifest>, bool)>,
+}
+
+struct PreviewWindow {
+    r#mod: GameMod,
+    preview: Result<ArchivePreview, String>,
+    /// Installed mods sharing a UniqueID or folder with the archive
+    replaces: Vec<String>,
+}
+
+struct LogView {
+    log: SmapiLog,
+    /// Active mod each problem belongs to, by mod and file id
+    links: Vec<Option<(u64, u64)>>,
+    level: LogLevel,
+    source: Option<String>,
+}
+
 #[derive(Default, PartialEq)]
 enum Menus {
     Browse,
     Downloading,
     #[default]
     Mods,
+    Console,
+    Logs,
+    Notifications,
     Settings,
 }
 
 pub struct SDMMApp {
     downloads_receiver: Receiver<(String, usize, usize, usize, usize)>,
-    web_client: reqwest::blocking::Client,
     state: Menus,
     download_path: PathBuf,
     game_path: PathBuf,
@@ -74,8 +244,82 @@ pub struct SDMMApp {
     needs_key: bool,
     downloads: HashMap<String, (usize, usize, usize, usize, bool)>,
     inactive: Vec<GameMod>,
-    active: Vec<GameMod>
-    // TODO: Add some kind of popups list that show
+    active: Vec<GameMod>,
+    preview: Option<PreviewWindow>,
+    /// Archives waiting for a pick of the mods to install, shown one at a time
+    install_choices: VecDeque<InstallChoice>,
+    disable_strategy: DisableStrategy,
+    store: ArchiveStore,
+    versions: Versions,
+    launch_method: LaunchMethod,
+    launch_args: String,
+    game: Option<GameProcess>,
+    log_view: Option<Result<LogView, String>>,
+    bisect: Option<Bisect>,
+    download_settings: SharedSettings,
+    download_concurrency: usize,
+    log_level: logging::Level,
+    dark_mode: bool,
+    update_check: UpdateCheck,
+    /// Unix time of the last update check
+    last_update_check: u64,
+    /// An update check ran since SDMM started
+    checked_updates: bool,
+    checking_updates: bool,
+    metadata: MetadataFetcher,
+    metadata_receiver: Receiver<Metadata>,
+    /// Packs unpacked on the download runtime, with the path they were dropped from
+    pack_sender: Sender<(PathBuf, Result<Extracted, Error>)>,
+    pack_receiver: Receiver<(PathBuf, Result<Extracted, Error>)>,
+    downloader: Downloader,
+    /// The API key belongs to a premium account
+    premium: bool,
+    import: Option<ImportReport>,
+    lock_report: Option<LockReport>,
+    comparison: Option<Comparison>,
+    notifications: Notifications,
+    /// Hands out notifications to the app and the download runtime
+    notifier: Notifier,
+    /// Questions blocking the rest of the app until answered, asked one at a time
+    confirmations: VecDeque<Confirmation<Confirm>>,
+    settings_edit: SettingsEdit,
+    /// Name of the installation `game_path`, `active` and `inactive` belong to
+    installation_name: String,
+    /// Every other installation, swapped with the current one when selected
+    installations: Vec<Installation>,
+    /// Cleared when the state file is from a newer SDMM, so it is not overwritten
+    save_state: bool,
+}
+
+/// What to do once a confirmation is accepted
+enum Confirm {
+    DeleteMod {
+        r#mod: Box<GameMod>,
+        is_active: bool,
+    },
+    ForgetInstallation(String),
+    /// Enables or disables a mod despite changed files or files shared with other mods
+    Switch {
+        r#mod: Box<GameMod>,
+        is_active: bool,
+    },
+    /// Deletes the disabled copies in the way of disabling this active mod, then disables it
+    ReplaceStash(Box<GameMod>),
+    SyncLockfile(Lockfile),
+    /// Sets up the active mods of an imported pack, the pack's name and its active mods
+    RestorePack(String, Vec<GameMod>),
+}
+
+/// Text typed into the settings page, only applied once it is valid
+#[derive(Default)]
+struct SettingsEdit {
+    game_path: String,
+    download_path: String,
+    api_key: String,
+    error: Option<String>,
+    installation_name: String,
+    new_installation_name: String,
+    new_installation_path: String,
 }
 
 impl SDMMApp {
@@ -97,37 +341,124 @@ impl SDMMApp {
             .unwrap()
             .insert(0, "CodeNewRoman".to_owned());
         context.egui_ctx.set_fonts(fonts);
-        context.egui_ctx.set_visuals(egui::Visuals::dark());
 
-        let download_path = setup_download_path(context);
+        let mut startup_errors = vec![];
+        let (state, save_state) = match state::load() {
+            Ok(Some(state)) => (state, true),
+            Ok(None) => (
+                context
+                    .storage
+                    .and_then(state::import_eframe)
+                    .unwrap_or_default(),
+                true,
+            ),
+            Err(e @ Error::StateTooNew(_)) => {
+                startup_errors.push(Error::Context(
+                    String::from("Changes made now will not be saved"),
+                    Box::new(e),
+                ));
+                (State::default(), false)
+            }
+            Err(e) => {
+                startup_errors.push(Error::Context(
+                    String::from("Failed to load the saved state"),
+                    Box::new(e),
+                ));
+                (State::default(), true)
+            }
+        };
+        let State {
+            settings,
+            current,
+            mut installations,
+            ..
+        } = state;
+        let selected = installations
+            .iter()
+            .position(|i| i.name == current)
+            .or((!installations.is_empty()).then_some(0));
+        let Installation {
+            name: installation_name,
+            mut game_path,
+            mut active,
+            mut inactive,
+        } = match selected {
+            Some(index) => installations.remove(index),
+            None => Installation {
+                name: String::from("Default"),
+                game_path: PathBuf::new(),
+                active: vec![],
+                inactive: vec![],
+            },
+        };
+        let Settings {
+            api_key,
+            download_path,
+            last_download: saved_download,
+            disable_strategy,
+            launch_method,
+            launch_args,
+            dark_mode,
+            update_check,
+            last_update_check,
+            download_concurrency,
+            log_level,
+        } = settings;
+        logging::set_level(log_level);
+        let download_path = setup_download_path(download_path).unwrap_or_else(|e| {
+            startup_errors.push(e);
+            pick_download_path()
+        });
+        let mut last_download = download.display().to_string();
+        if last_download.is_empty() {
+            last_download = saved_download;
+        }
 
-        let web_client = reqwest::blocking::Client::new();
+        if game_path.as_os_str().is_empty() {
+            game_path = locate_game_path();
+        }
 
-        let mut active: Vec<GameMod> = vec![];
-        let mut inactive: Vec<GameMod> = vec![];
-        let mut game_path = PathBuf::new();
-        let mut last_download = download.display().to_string();
-        let mut api_key = String::new();
-        if let Some(storage) = context.storage {
-            let loaded = load_from_storage(storage, &web_client);
-            active = loaded.0;
-            inactive = loaded.1;
-            game_path = loaded.2;
-            if last_download.is_empty() {
-                last_download = loaded.3;
+        let mut store = ArchiveStore::open_or_import(&download_path);
+        let others = installations
+            .iter_mut()
+            .flat_map(|i| i.active.iter_mut().chain(i.inactive.iter_mut()));
+        for r#mod in active.iter_mut().chain(inactive.iter_mut()).chain(others) {
+            if r#mod.hash.is_empty()
+                && let Some(hash) = store.find_by_name(&r#mod.zip_name)
+            {
+                store.add_source(&hash, r#mod.mod_id, r#mod.file_id);
+                r#mod.hash = hash;
             }
-            api_key = loaded.4;
         }
 
+        context.egui_ctx.set_visuals(if dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
+
         let (sync_sender, receiver) = sync_channel::<(String, usize, usize, usize, usize)>(1);
-        let download_path_clone = download_path.clone();
+        let download_settings = Arc::new(RwLock::new(DownloadSettings {
+            api_key: api_key.clone(),
+            download_path: download_path.clone(),
+            concurrency: download_concurrency,
+        }));
         // TODO: Continue downloads that weren't finished previously?
-        handle_download_requests(
+        let (notifications, notifier) = Notifications::new(context.egui_ctx.clone());
+        let downloader = handle_download_requests(
             sync_sender,
-            download_path,
-            api_key.clone(),
+            notifier.clone(),
+            download_settings.clone(),
             last_download.clone(),
         );
+        let (metadata, metadata_receiver) = MetadataFetcher::new(
+            downloader.runtime(),
+            download_settings.clone(),
+            context.egui_ctx.clone(),
+            notifier.clone(),
+        );
+
+        let (pack_sender, pack_receiver) = channel();
 
         let mut needs_key = true;
         if !api_key.is_empty() {
@@ -135,19 +466,63 @@ impl SDMMApp {
             last_download = String::new();
         }
 
-        SDMMApp {
+        let mut app = SDMMApp {
             downloads_receiver: receiver,
-            web_client,
             state: Menus::default(),
-            download_path: download_path_clone,
+            download_path,
             game_path,
             last_download: PathBuf::from(last_download),
             api_key,
             needs_key,
             downloads: HashMap::new(),
             inactive,
-            active
+            active,
+            preview: None,
+            install_choices: VecDeque::new(),
+            disable_strategy,
+            store,
+            versions: Versions::default(),
+            launch_method,
+            launch_args,
+            game: None,
+            log_view: None,
+            bisect: None,
+            download_settings,
+            download_concurrency,
+            log_level,
+            dark_mode,
+            update_check,
+            last_update_check,
+            checked_updates: false,
+            checking_updates: false,
+            metadata,
+            metadata_receiver,
+            pack_sender,
+            pack_receiver,
+            downloader,
+            premium: false,
+            import: None,
+            lock_report: None,
+            comparison: None,
+            notifications,
+            notifier,
+            confirmations: VecDeque::new(),
+            settings_edit: SettingsEdit::default(),
+            installation_name,
+            installations,
+            save_state,
+        };
+        for e in startup_errors {
+            app.notifier.error(e);
         }
+        app.reset_settings_edit();
+        app.scan_mods_folder();
+        app.refresh_versions();
+        app.fetch_missing_metadata();
+        if !app.api_key.is_empty() {
+            app.metadata.account();
+        }
+        app
     }
 
     fn mods_display(&mut self, ctx: &egui::Context) {
@@ -241,6 +616,7 @@ impl SDMMApp {
                                                     } else {
                                                         ui.label(&r#mod.name);
                                                     }
+                                                    mod_markers(ui, r#mod);
                                                     let sense = ui.interact(
                                                         ui.max_rect(),
                                                         egui::Id::new(&format!(
@@ -252,9 +628,11 @@ impl SDMMApp {
                                                     if sense.double_clicked()
                                                         || sense.triple_clicked()
                                                     {
-                                                        self.switch_active_inactive(
+                                                        if let Err(e) = self.switch_active_inactive(
                                                             r#mod, index, false,
-                                                        );
+                                                        ) {
+                                                            self.notifier.error(e);
+                                                        }
                                                     }
                                                     self.show_context_menu(
                                                         sense, r#mod, index, false,
@@ -273,9 +651,11 @@ impl SDMMApp {
                                                     if sense.double_clicked()
                                                         || sense.triple_clicked()
                                                     {
-                                                        self.switch_active_inactive(
+                                                        if let Err(e) = self.switch_active_inactive(
                                                             r#mod, index, false,
-                                                        );
+                                                        ) {
+                                                            self.notifier.error(e);
+                                                        }
                                                     }
                                                     self.show_context_menu(
                                                         sense, r#mod, index, false,
@@ -294,9 +674,11 @@ impl SDMMApp {
                                                     if sense.double_clicked()
                                                         || sense.triple_clicked()
                                                     {
-                                                        self.switch_active_inactive(
+                                                        if let Err(e) = self.switch_active_inactive(
                                                             r#mod, index, false,
-                                                        );
+                                                        ) {
+                                                            self.notifier.error(e);
+                                                        }
                                                     }
                                                     self.show_context_menu(
                                                         sense, r#mod, index, false,
@@ -396,6 +778,7 @@ impl SDMMApp {
                                                     } else {
                                                         ui.label(&r#mod.name);
                                                     }
+                                                    mod_markers(ui, r#mod);
                                                     let sense = ui.interact(
                                                         ui.max_rect(),
                                                         egui::Id::new(&format!(
@@ -407,9 +790,11 @@ impl SDMMApp {
                                                     if sense.double_clicked()
                                                         || sense.triple_clicked()
                                                     {
-                                                        self.switch_active_inactive(
+                                                        if let Err(e) = self.switch_active_inactive(
                                                             r#mod, index, true,
-                                                        );
+                                                        ) {
+                                                            self.notifier.error(e);
+                                                        }
                                                     }
                                                     self.show_context_menu(
                                                         sense, r#mod, index, true,
@@ -428,9 +813,11 @@ impl SDMMApp {
                                                     if sense.double_clicked()
                                                         || sense.triple_clicked()
                                                     {
-                                                        self.switch_active_inactive(
+                                                        if let Err(e) = self.switch_active_inactive(
                                                             r#mod, index, true,
-                                                        );
+                                                        ) {
+                                                            self.notifier.error(e);
+                                                        }
                                                     }
                                                     self.show_context_menu(
                                                         sense, r#mod, index, true,
@@ -449,9 +836,11 @@ impl SDMMApp {
                                                     if sense.double_clicked()
                                                         || sense.triple_clicked()
                                                     {
-                                                        self.switch_active_inactive(
+                                                        if let Err(e) = self.switch_active_inactive(
                                                             r#mod, index, true,
-                                                        );
+                                                        ) {
+                                                            self.notifier.error(e);
+                                                        }
                                                     }
                                                     self.show_context_menu(
                                                         sense, r#mod, index, true,
@@ -471,10 +860,280 @@ impl SDMMApp {
             });
         });
         egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
-            ui.label("Double click a mod to activate or deactivate it, you can right click a mod to delete it.");
+            ui.horizontal(|ui| {
+                if ui.button("Scan Mods folder").clicked() {
+                    self.scan_mods_folder();
+                }
+                if ui
+                    .add_enabled(self.bisect.is_none(), egui::Button::new("Find problem mod"))
+                    .on_hover_text("Test halves of the mods to find the one breaking the game")
+                    .clicked()
+                {
+                    self.start_bisect();
+                }
+                ui.menu_button("Export mod list", |ui| {
+                    if ui.button("Active mods").clicked() {
+                        self.export_modlist(false);
+                        ui.close_menu();
+                    }
+                    if ui.button("All mods").clicked() {
+                        self.export_modlist(true);
+                        ui.close_menu();
+                    }
+                });
+                if ui.button("Import mod list").clicked() {
+                    self.import_modlist();
+                }
+                if ui
+                    .button("Export pack")
+                    .on_hover_text("Saves the mods with their archives, drop the pack here to import it")
+                    .clicked()
+                {
+                    self.export_pack();
+                }
+                if ui
+                    .button("Compare")
+                    .on_hover_text("Shows how another player's mod list or lockfile differs")
+                    .clicked()
+                {
+                    self.compare_modlist();
+                }
+                ui.menu_button("Lockfile", |ui| {
+                    if ui
+                        .button("Save")
+                        .on_hover_text("Records the exact archives of the active mods")
+                        .clicked()
+                    {
+                        self.save_lockfile();
+                        ui.close_menu();
+                    }
+                    if ui.button("Verify").clicked() {
+                        if let Some(lockfile) = self.pick_lockfile() {
+                            self.verify_lockfile(lockfile);
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Sync").clicked() {
+                        if let Some(lockfile) = self.pick_lockfile() {
+                            self.confirm_sync(lockfile);
+                        }
+                        ui.close_menu();
+                    }
+                });
+                ui.label("Double click a mod to activate or deactivate it, you can right click a mod to delete it.");
+            });
         });
     }
 
+    /// Picks up mods that were put in the Mods folder by hand and flags tracked mods whose
+    /// folders have disappeared
+    fn scan_mods_folder(&mut self) {
+        let mods_path = self.game_path.join("mods");
+        if !mods_path.is_dir() {
+            return;
+        }
+        for r#mod in self.active.iter_mut() {
+            if r#mod.mod_id == SMAPI_MOD_ID {
+                continue;
+            }
+            let folders = r#mod.folder_list();
+            r#mod.missing = !folders
+                .iter()
+                .any(|f| !f.is_empty() && mods_path.join(f).is_dir());
+        }
+        let known: HashSet<String> = self
+            .active
+            .iter()
+            .flat_map(|m| m.folders.iter().chain(std::iter::once(&m.folder_name)))
+            .cloned()
+            .collect();
+        let mut found = vec![];
+        find_manifests(&mods_path, Path::new(""), &mut found);
+        found.retain(|(folder, _)| !known.contains(folder));
+        if found.is_empty() {
+            return;
+        }
+        // Older entries never recorded their ids, look inside each of their archives once
+        let mut archive_ids: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        for m in self.inactive.iter_mut() {
+            if !m.unique_ids.is_empty() || m.zip_name.is_empty() {
+                continue;
+            }
+            let path = archive_path(&self.store, m);
+            let ids = archive_ids.entry(path).or_insert_with_key(|path| {
+                File::open(path)
+                    .map_err(Error::from)
+                    .and_then(|file| preview(&file, &m.fallback_name()))
+                    .map(|preview| {
+                        preview
+                            .units
+                            .into_iter()
+                            .filter_map(|(_, manifest)| manifest)
+                            .map(|manifest| manifest.unique_id)
+                            .filter(|id| !id.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            });
+            m.unique_ids = ids.clone();
+        }
+        while !found.is_empty() {
+            let (folder, manifest) = found.remove(0);
+            let position = self.inactive.iter().position(|m| {
+                m.unique_ids
+                    .iter()
+                    .any(|id| same_unique_id(id, &manifest.unique_id))
+            });
+            if let Some(index) = position {
+                let mut r#mod = self.inactive.remove(index);
+                // The other parts of its archive were most likely put in along with this one
+                let previous = r#mod.folder_list();
+                let mut folders = vec![folder];
+                found.retain(|(other, manifest)| {
+                    let same = previous.contains(other)
+                        || r#mod
+                            .unique_ids
+                            .iter()
+                            .any(|id| same_unique_id(id, &manifest.unique_id));
+                    if same {
+                        folders.push(other.clone());
+                    }
+                    !same
+                });
+                r#mod.folder_name = folders[0].clone();
+                r#mod.folders = folders;
+                r#mod.stashed = false;
+                self.active.push(r#mod);
+                continue;
+            }
+            let id = self.next_local_id();
+            let link = manifest
+                .update_keys
+                .iter()
+                .find_map(|key| key.strip_prefix("Nexus:"))
+                .map(|id| format!("https://www.nexusmods.com/stardewvalley/mods/{}", id.trim()))
+                .unwrap_or_default();
+            self.active.push(GameMod {
+                name: manifest.name,
+                folder_name: folder.clone(),
+                version: manifest.version,
+                author: manifest.author,
+                link,
+                mod_id: id,
+                file_id: id,
+                folders: vec![folder],
+                unique_ids: Some(manifest.unique_id)
+                    .filter(|id| !id.is_empty())
+                    .into_iter()
+                    .collect(),
+                external: true,
+                ..Default::default()
+            });
+        }
+    }
+
+    fn archive_path(&self, r#mod: &GameMod) -> PathBuf {
+        archive_path(&self.store, r#mod)
+    }
+
+    fn refresh_versions(&mut self) {
+        self.versions = Versions::detect(&self.game_path);
+        self.check_api_versions();
+    }
+
+    /// Flags active mods whose manifest asks for a newer SMAPI than the installed one
+    fn check_api_versions(&mut self) {
+        let mods_path = self.game_path.join("mods");
+        let smapi = self.versions.smapi.clone();
+        for r#mod in self.active.iter_mut() {
+            r#mod.needs_api = None;
+            let Some(smapi) = &smapi else {
+                continue;
+            };
+            for folder in r#mod.folder_list() {
+                let Some(required) =
+                    read_manifest(&mods_path.join(folder)).and_then(|m| m.minimum_api_version)
+                else {
+                    continue;
+                };
+                if compare_versions(&required, smapi).is_gt()
+                    && r#mod
+                        .needs_api
+                        .as_ref()
+                        .map_or(true, |current| compare_versions(&required, current).is_gt())
+                {
+                    r#mod.needs_api = Some(required);
+                }
+            }
+        }
+    }
+
+    /// Mods without a Nexus id get the lowest free id from the top of the range so they never
+    /// collide
+    fn next_local_id(&self) -> u64 {
+        let used: HashSet<u64> = self
+            .library()
+            .map(|m| m.mod_id)
+            .filter(|id| *id >= HIGH)
+            .collect();
+        (HIGH..=u64::MAX)
+            .find(|id| !used.contains(id))
+            .unwrap_or(HIGH)
+    }
+
+    /// Every mod of every installation
+    fn library(&self) -> impl Iterator<Item = &GameMod> {
+        let others = self
+            .installations
+            .iter()
+            .flat_map(|i| i.active.iter().chain(i.inactive.iter()));
+        self.active.iter().chain(self.inactive.iter()).chain(others)
+    }
+
+    fn library_mut(&mut self) -> impl Iterator<Item = &mut GameMod> {
+        let others = self
+            .installations
+            .iter_mut()
+            .flat_map(|i| i.active.iter_mut().chain(i.inactive.iter_mut()));
+        self.active
+            .iter_mut()
+            .chain(self.inactive.iter_mut())
+            .chain(others)
+    }
+
+    /// Adds a new archive as an inactive mod to every installation
+    fn add_to_library(&mut self, r#mod: GameMod) {
+        for installation in &mut self.installations {
+            let known = installation
+                .active
+                .iter()
+                .chain(installation.inactive.iter())
+                .any(|m| {
+                    m.hash == r#mod.hash || (m.mod_id, m.file_id) == (r#mod.mod_id, r#mod.file_id)
+                });
+            if !known {
+                installation.inactive.push(r#mod.library_copy());
+            }
+        }
+        self.inactive.push(r#mod);
+    }
+
+    fn switch_installation(&mut self, index: usize) {
+        let next = self.installations.remove(index);
+        let current = Installation {
+            name: std::mem::replace(&mut self.installation_name, next.name),
+            game_path: std::mem::replace(&mut self.game_path, next.game_path),
+            active: std::mem::replace(&mut self.active, next.active),
+            inactive: std::mem::replace(&mut self.inactive, next.inactive),
+        };
+        self.installations.insert(index, current);
+        self.preview = None;
+        self.install_choices.clear();
+        self.game = None;
+        self.reset_settings_edit();
+        self.set_game_path(self.game_path.clone());
+    }
+
     fn show_context_menu(
         &mut self,
         sense: egui::Response,
@@ -483,31 +1142,194 @@ impl SDMMApp {
         is_active: bool,
     ) {
         sense.context_menu(|ui| {
+            if r#mod.external {
+                ui.label("Installed outside of SDMM");
+                let text = if is_active { "Disable" } else { "Enable" };
+                if ui.button(text).clicked() {
+                    if let Err(e) = self.switch_active_inactive(r#mod, index, is_active) {
+                        self.notifier.error(e);
+                    }
+                }
+                if ui
+                    .button("Stop tracking")
+                    .on_hover_text("Leaves its files where they are")
+                    .clicked()
+                {
+                    self.active.retain(|m| m.mod_id != r#mod.mod_id);
+                    self.inactive.retain(|m| m.mod_id != r#mod.mod_id);
+                    ui.close_menu();
+                }
+                return;
+            }
             let text = if is_active { "Disable" } else { "Enable" };
             if ui.button(text).clicked() {
-                self.switch_active_inactive(r#mod, index, is_active);
+                if let Err(e) = self.switch_active_inactive(r#mod, index, is_active) {
+                    self.notifier.error(e);
+                }
+            }
+            if ui.button("Details").clicked() {
+                self.open_preview(r#mod.clone());
+                ui.close_menu();
+            }
+            if !is_active && !r#mod.units.is_empty() && ui.button("Change install choice").clicked()
+            {
+                self.open_install_choice(r#mod.clone());
+                ui.close_menu();
             }
             if ui.button("Delete").clicked() {
-                if is_active {
-                    self.switch_active_inactive(r#mod, index, is_active);
+                self.confirmations.push_back(Confirmation {
+                    title: format!("Delete {}?", r#mod.name),
+                    message: String::from(
+                        "It is removed from every installation's library and its archive is \
+                         deleted, unless another installation has it enabled.",
+                    ),
+                    confirm: String::from("Delete"),
+                    action: Confirm::DeleteMod {
+                        r#mod: Box::new(r#mod.clone()),
+                        is_active,
+                    },
+                });
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn delete_mod(&mut self, mut r#mod: GameMod, is_active: bool) {
+        if is_active
+            && let Some(index) = self.active.iter().position(|m| {
+                m.zip_name == r#mod.zip_name
+                    && m.mod_id == r#mod.mod_id
+                    && m.file_id == r#mod.file_id
+            })
+            && let Err(e) = self.switch_confirmed(&mut r#mod, index, is_active)
+        {
+            self.notifier.error(e);
+        }
+        for (i, gmod) in self.inactive.iter().enumerate() {
+            if gmod.name == r#mod.name && gmod.version == r#mod.version {
+                self.inactive.remove(i);
+                break;
+            }
+        }
+        if r#mod.stashed {
+            let mods_path = self.game_path.join("mods");
+            if let Err(e) = disable::discard(&mods_path, &r#mod.folder_list()) {
+                self.notifier.error(format!(
+                    "Failed to delete disabled copy of {}: {e}",
+                    r#mod.name
+                ));
+            }
+        }
+        // Drop the copies `add_to_library` gave the other installations, only mods that are
+        // installed there keep the archive
+        let same = |m: &GameMod| {
+            if r#mod.hash.is_empty() {
+                (m.mod_id, m.file_id, &m.zip_name) == (r#mod.mod_id, r#mod.file_id, &r#mod.zip_name)
+            } else {
+                m.hash == r#mod.hash
+            }
+        };
+        for installation in &mut self.installations {
+            installation.inactive.retain(|m| m.stashed || !same(m));
+        }
+        let used_by = self
+            .installations
+            .iter()
+            .filter(|i| {
+                i.active
+                    .iter()
+                    .chain(i.inactive.iter().filter(|m| m.stashed))
+                    .any(same)
+            })
+            .map(|i| i.name.clone())
+            .chain(
+                self.active
+                    .iter()
+                    .any(same)
+                    .then(|| self.installation_name.clone()),
+            )
+            .collect::<Vec<_>>();
+        let mod_path = self.archive_path(&r#mod);
+        let result = if !used_by.is_empty() {
+            self.notifier.info(format!(
+                "Kept the archive of {}, it is still installed in {}",
+                r#mod.name,
+                used_by.join(", ")
+            ));
+            Ok(())
+        } else if !r#mod.hash.is_empty() {
+            self.store.remove(&r#mod.hash)
+        } else {
+            remove_file(&mod_path)
+        };
+        if let Err(e) = result {
+            self.notifier.error(format!(
+                "Failed to delete mod {} at {}: {}",
+                r#mod.name,
+                &mod_path.display(),
+                e
+            ));
+        }
+    }
+
+    /// Shows the first queued confirmation and carries out its action once accepted
+    fn confirmation_display(&mut self, ctx: &egui::Context) {
+        let Some(answer) = self.confirmations.front().and_then(|c| c.show(ctx)) else {
+            return;
+        };
+        let Some(confirmation) = self.confirmations.pop_front() else {
+            return;
+        };
+        if !answer {
+            return;
+        }
+        match confirmation.action {
+            Confirm::DeleteMod { r#mod, is_active } => self.delete_mod(*r#mod, is_active),
+            Confirm::ForgetInstallation(name) => {
+                self.installations.retain(|i| i.name != name);
+            }
+            Confirm::Switch { r#mod, is_active } => {
+                let list = if is_active {
+                    &self.active
+                } else {
+                    &self.inactive
+                };
+                let same = |m: &GameMod| {
+                    (m.mod_id, m.file_id, &m.hash) == (r#mod.mod_id, r#mod.file_id, &r#mod.hash)
+                };
+                if let Some(index) = list.iter().position(same) {
+                    let mut r#mod = list[index].clone();
+                    if let Err(e) = self.switch_confirmed(&mut r#mod, index, is_active) {
+                        self.notifier.error(e);
+                    }
                 }
-                for (i, gmod) in self.inactive.iter().enumerate() {
-                    if gmod.name == r#mod.name && gmod.version == r#mod.version {
-                        self.inactive.remove(i);
-                        break;
+            }
+            Confirm::ReplaceStash(r#mod) => {
+                let mods_path = self.game_path.join("mods");
+                let folders = r#mod.folder_list();
+                if let Err(e) = disable::discard(&mods_path, &folders) {
+                    self.notifier
+                        .error(format!("Failed to delete disabled copies: {e}"));
+                    return;
+                }
+                for other in self.inactive.iter_mut() {
+                    if other.stashed && other.folder_list().iter().any(|f| folders.contains(f)) {
+                        other.stashed = false;
                     }
                 }
-                let mod_path = self.download_path.join(&r#mod.zip_name);
-                if let Err(e) = remove_file(&mod_path) {
-                    eprintln!(
-                        "Failed to delete mod {} at {}: {}",
-                        r#mod.name,
-                        &mod_path.display(),
-                        e
-                    );
+                let same = |m: &GameMod| {
+                    (m.mod_id, m.file_id, &m.hash) == (r#mod.mod_id, r#mod.file_id, &r#mod.hash)
+                };
+                if let Some(index) = self.active.iter().position(same) {
+                    let mut r#mod = self.active[index].clone();
+                    if let Err(e) = self.switch_active_inactive(&mut r#mod, index, true) {
+                        self.notifier.error(e);
+                    }
                 }
             }
-        });
+            Confirm::SyncLockfile(lockfile) => self.sync_lockfile(lockfile),
+            Confirm::RestorePack(name, active) => self.restore_pack(name, active),
+        }
     }
 
     fn downloads_display(&mut self, ctx: &egui::Context) {
@@ -516,13 +1338,19 @@ impl SDMMApp {
                 ui.heading("Downloads");
                 ui.separator();
                 let mut removal: Vec<String> = vec![];
+                let mut details: Option<String> = None;
                 for (mod_name, (downloaded, total, _, _, _)) in self.downloads.iter_mut() {
                     ui.heading(mod_name);
                     let mut animate = true;
                     if downloaded == total {
-                        if ui.button("X").clicked() {
-                            removal.push(mod_name.clone());
-                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("X").clicked() {
+                                removal.push(mod_name.clone());
+                            }
+                            if ui.button("Details").clicked() {
+                                details = Some(mod_name.clone());
+                            }
+                        });
                         animate = false;
                     }
                     ui.add(
@@ -534,222 +1362,2329 @@ impl SDMMApp {
                 for name in removal {
                     self.downloads.remove(&name);
                 }
+                if let Some(zip_name) = details {
+                    let r#mod = self
+                        .inactive
+                        .iter()
+                        .chain(self.active.iter())
+                        .find(|m| m.zip_name == zip_name)
+                        .cloned()
+                        .unwrap_or(GameMod {
+                            name: zip_name.clone(),
+                            zip_name,
+                            ..Default::default()
+                        });
+                    self.open_preview(r#mod);
+                }
             });
         });
     }
 
-    fn browse(&mut self, ctx: &egui::Context) {
-        // TODO: Panel for browsing Mods:
-        //      Some mods will be taken from sources like github if they have releases available.
-        //      Possible that I may build and host versions myself from github to improve
-        //      simplicity.
-        //      All other mods will just require opening the NexusMods page and downloading
-        //          MAYBE: unless a premium user that can oauth using the app and then select
-        //              and download from inside the app.
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Browse");
-            ui.separator();
-            ui.heading("COMING SOON");
+    fn open_preview(&mut self, r#mod: GameMod) {
+        let preview = File::open(self.archive_path(&r#mod))
+            .map_err(Error::from)
+            .and_then(|file| preview(&file, &r#mod.fallback_name()))
+            .map_err(|e| e.to_string());
+        let mut replaces = vec![];
+        if let Ok(preview) = &preview {
+            let mods_path = self.game_path.join("mods");
+            for installed in &self.active {
+                if installed.zip_name == r#mod.zip_name {
+                    continue;
+                }
+                let overlaps = installed.folders.iter().any(|folder| {
+                    let installed = read_manifest(&mods_path.join(folder));
+                    preview.units.iter().any(|(unit, manifest)| {
+                        let same_id = match (manifest, &installed) {
+                            (Some(a), Some(b)) => same_unique_id(&a.unique_id, &b.unique_id),
+                            _ => false,
+                        };
+                        unit.folder_name == *folder || same_id
+                    })
+                });
+                if overlaps {
+                    replaces.push(format!("{} {}", installed.name, installed.version));
+                }
+            }
+        }
+        self.preview = Some(PreviewWindow {
+            r#mod,
+            preview,
+            replaces,
         });
     }
 
-    fn switch_active_inactive(&mut self, r#mod: &mut GameMod, index: usize, is_active: bool) {
-        if is_active {
-            let mods_path = if r#mod.mod_id != 2400 {
-                self.game_path.join("mods")
-            } else {
-                #[cfg(target_os = "windows")]
-                {
-                    let smapi_path = self
-                        .game_path
-                        .clone()
-                        .join(&r#mod.folder_name)
-                        .join("internal\\windows");
-                    let file = File::open(smapi_path.join("install.dat")).unwrap();
-                    let files = list_files(&file);
-                    for (file, is_dir) in files {
-                        if !file.starts_with("Mods") {
-                            if is_dir {
-                                match remove_dir_all(self.game_path.join(&file)) {
-                                    Ok(_) => {}
-                                    Err(e) => {
-                                        if e.kind() != io::ErrorKind::NotFound {
-                                            eprintln!(
-                                                "Failed to remove directory {} at {}: {e}",
-                                                file,
-                                                self.game_path.join(&file).display()
-                                            )
-                                        }
-                                    }
-                                }
-                            } else {
-                                match remove_file(self.game_path.join(&file)) {
-                                    Ok(_) => {}
-                                    Err(e) => {
-                                        if e.kind() != io::ErrorKind::NotFound {
-                                            eprintln!(
-                                                "Failed to remove file {} at {}: {e}",
-                                                file,
-                                                self.game_path.join(&file).display()
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    match remove_file(self.game_path.join("StardewModdingAPI.deps.json")) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            if e.kind() != io::ErrorKind::NotFound {
-                                eprintln!("Failed to remove file: {e}")
-                            }
+    fn open_install_choice(&mut self, r#mod: GameMod) {
+        let queued = self.install_choices.iter().any(|c| {
+            (c.r#mod.zip_name.as_str(), c.r#mod.mod_id, c.r#mod.file_id)
+                == (r#mod.zip_name.as_str(), r#mod.mod_id, r#mod.file_id)
+        });
+        if queued {
+            return;
+        }
+        let preview = match File::open(self.archive_path(&r#mod))
+            .map_err(Error::from)
+            .and_then(|file| preview(&file, &r#mod.fallback_name()))
+        {
+            Ok(preview) => preview,
+            Err(e) => {
+                self.notifier.error(Error::Context(
+                    format!("Failed to read mod {}", r#mod.name),
+                    Box::new(e),
+                ));
+                return;
+            }
+        };
+        let mut seen: Vec<String> = vec![];
+        let units = preview
+            .units
+            .into_iter()
+            .map(|(unit, manifest)| {
+                let checked = if r#mod.units.is_empty() {
+                    // Units sharing a UniqueID are variants of one mod, only one can be installed
+                    match &manifest {
+                        Some(m) if seen.contains(&m.unique_id) => false,
+                        Some(m) => {
+                            seen.push(m.unique_id.clone());
+                            true
                         }
+                        None => true,
                     }
-                }
-                #[cfg(target_os = "linux")]
-                {
-                    let executable = self
-                        .game_path
-                        .clone()
-                        .join(&r#mod.folder_name)
-                        .join("internal/linux/SMAPI.Installer")
-                        .display()
-                        .to_string();
-                    let mut installer = Command::new(executable)
-                        .stdin(Stdio::piped())
-                        .spawn()
-                        .unwrap();
-                    let stdin = installer.stdin.as_mut().unwrap();
-                    stdin.write(b"\n").unwrap();
-                    stdin.write(b"2\n").unwrap();
-                    stdin
-                        .write_all(self.game_path.display().to_string().as_bytes())
-                        .unwrap();
-                    stdin.write(b"\n").unwrap();
-                    stdin.write(b"2\n").unwrap();
-                    stdin.write(b"\n").unwrap();
-                    let _ = installer.wait();
-                }
-                self.game_path.clone()
-            };
-            let mod_path = mods_path.join(&r#mod.folder_name);
-            if let Err(e) = remove_dir_all(mod_path) {
-                eprintln!("Failed to remove mod: {}", e);
-            }
-            self.inactive.push(r#mod.clone());
-            self.active.remove(index);
+                } else {
+                    r#mod.units.contains(&unit.root.display().to_string())
+                };
+                (unit, manifest, checked)
+            })
+            .collect();
+        self.install_choices
+            .push_back(InstallChoice { r#mod, units });
+    }
+
+    fn export_modlist(&mut self, all: bool) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("modlist.json")
+            .add_filter("Mod list", &["json"])
+            .save_file()
+        else {
+            return;
+        };
+        let mods: Vec<&GameMod> = if all {
+            self.active.iter().chain(self.inactive.iter()).collect()
         } else {
-            if self.active.iter().any(|m| m.mod_id == r#mod.mod_id) {
-                println!("Already exists? {}", r#mod.mod_id);
+            self.active.iter().collect()
+        };
+        let entries = mods.into_iter().map(|m| ListEntry::new(m, HIGH)).collect();
+        match modlist::save(&path, entries) {
+            Ok(()) => self
+                .notifier
+                .info(format!("Saved the mod list to {}", path.display())),
+            Err(e) => self
+                .notifier
+                .error(format!("Failed to save the mod list: {e}")),
+        }
+    }
+
+    /// Looks up every entry of a mod list in the library, downloading or opening the pages of
+    /// the NexusMods ones that are missing
+    fn import_modlist(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Mod list", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
+        let list = match modlist::load(&path) {
+            Ok(list) => list,
+            Err(e) => {
+                self.notifier.error(Error::Context(
+                    format!("Failed to read {}", path.display()),
+                    Box::new(e),
+                ));
                 return;
             }
-            let mods_path = if r#mod.mod_id != 2400 {
-                self.game_path.join("mods")
+        };
+        let mut entries = vec![];
+        for entry in list.mods {
+            let status = if self.active.iter().any(|m| entry.matches(m)) {
+                ImportStatus::Active
+            } else if self.inactive.iter().any(|m| entry.matches(m)) {
+                ImportStatus::Found
+            } else if let Some(nxm) = entry.nxm()
+                && self.premium
+            {
+                self.downloader.start(nxm);
+                ImportStatus::Downloading
+            } else if entry.page().is_some() {
+                ImportStatus::Manual
             } else {
-                self.game_path.clone()
+                ImportStatus::Unmatched
             };
-            let file = File::open(self.download_path.join(&r#mod.zip_name)).unwrap();
-            r#mod.folder_name = unzip(&file, &mods_path);
-            // TODO: Handle installing smapi and updating
-            if r#mod.mod_id == 2400 {
-                #[cfg(target_os = "windows")]
-                {
-                    let smapi_path = mods_path.join(&r#mod.folder_name).join("internal\\windows");
-                    let file = File::open(smapi_path.join("install.dat")).unwrap();
-                    unzip(&file, &mods_path);
-                    let file = File::open(self.game_path.join("Stardew Valley.deps.json")).unwrap();
-                    let mut dup =
-                        File::create(self.game_path.join("StardewModdingAPI.deps.json")).unwrap();
-                    dup.write_all(&(file.bytes().map(|b| b.ok().unwrap()).collect::<Vec<u8>>()))
-                        .unwrap();
-                }
-                #[cfg(target_os = "linux")]
+            entries.push((entry, status));
+        }
+        let unmatched = entries
+            .iter()
+            .filter(|(_, status)| *status == ImportStatus::Unmatched)
+            .count();
+        if unmatched > 0 {
+            self.notifier
+                .warning(format!("{unmatched} mods of the list could not be found"));
+        }
+        self.import = Some(ImportReport { entries });
+    }
+
+    fn import_display(&mut self, ctx: &egui::Context) {
+        let Some(mut report) = self.import.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut enable = false;
+        egui::Window::new("Import mod list")
+            .id(egui::Id::new("import"))
+            .open(&mut open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                for (entry, status) in &report.entries {
+                    ui.horizontal(|ui| {
+                        ui.strong(&entry.name);
+                        ui.label(&entry.version);
+                        match status {
+                            ImportStatus::Active => {
+                                ui.weak("Active");
+                            }
+                            ImportStatus::Found => {
+                                ui.label("In the library");
+                            }
+                            ImportStatus::Downloading => {
+                                ui.label("Downloading");
+                            }
+                            ImportStatus::Manual => {
+                                if let Some(page) = entry.page()
+                                    && ui
+                                        .button("Open page")
+                                        .on_hover_text("Free accounts download from the website")
+                                        .clicked()
+                                    && let Err(e) = launch::open(&page)
+                                {
+                                    self.notifier.error(format!("Failed to open {page}: {e}"));
+                                }
+                            }
+                            ImportStatus::Unmatched => {
+                                ui.colored_label(egui::Color32::RED, "Not found");
+                            }
+                        }
+                    });
+                }
+                ui.separator();
+                let found = report
+                    .entries
+                    .iter()
+                    .any(|(_, status)| *status == ImportStatus::Found);
+                if ui
+                    .add_enabled(found, egui::Button::new("Enable found mods"))
+                    .clicked()
                 {
-                    let executable = mods_path
-                        .join(&r#mod.folder_name)
-                        .join("internal/linux/SMAPI.Installer")
-                        .display()
-                        .to_string();
-                    let mut installer = Command::new(executable)
-                        .stdin(Stdio::piped())
-                        .spawn()
-                        .unwrap();
-                    let stdin = installer.stdin.as_mut().unwrap();
-                    stdin.write(b"\n").unwrap();
-                    stdin.write(b"2\n").unwrap();
-                    stdin
-                        .write_all(self.game_path.display().to_string().as_bytes())
-                        .unwrap();
-                    stdin.write(b"\n").unwrap();
-                    stdin.write(b"1\n").unwrap();
-                    stdin.write(b"\n").unwrap();
+                    enable = true;
+                }
+            });
+        if enable {
+            for (entry, status) in report.entries.iter_mut() {
+                if *status != ImportStatus::Found {
+                    continue;
+                }
+                let Some(index) = self.inactive.iter().position(|m| entry.matches(m)) else {
+                    continue;
+                };
+                let mut r#mod = self.inactive[index].clone();
+                if let Err(e) = self.switch_active_inactive(&mut r#mod, index, false) {
+                    self.notifier.error(e);
+                }
+                if self.active.iter().any(|m| entry.matches(m)) {
+                    *status = ImportStatus::Active;
+                }
+            }
+        }
+        if open {
+            self.import = Some(report);
+        }
+    }
+
+    fn export_pack(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name(&format!("{}.{PACK_EXTENSION}", self.installation_name))
+            .add_filter("SDMM pack", &[PACK_EXTENSION])
+            .save_file()
+        else {
+            return;
+        };
+        let copies = |mods: &[GameMod]| -> Vec<GameMod> {
+            mods.iter()
+                .filter(|m| !m.external)
+                .map(GameMod::library_copy)
+                .collect()
+        };
+        let result = pack::export(
+            &path,
+            self.installation_name.clone(),
+            copies(&self.active),
+            copies(&self.inactive),
+            &self.store,
+        );
+        match result {
+            Ok(()) => self
+                .notifier
+                .info(format!("Saved the pack to {}", path.display())),
+            Err(e) => self.notifier.error(format!("Failed to save the pack: {e}")),
+        }
+    }
+
+    /// Unpacks a pack on the download runtime, `receive_packs` picks it up once it is done
+    fn import_pack(&mut self, path: PathBuf, ctx: &egui::Context) {
+        self.notifier.info(format!("Importing {}", path.display()));
+        let partial = self.store.root().join(PARTIAL_FOLDER);
+        let known: HashSet<String> = self.library().map(|m| m.hash.clone()).collect();
+        let (sender, ctx) = (self.pack_sender.clone(), ctx.clone());
+        self.downloader.runtime().spawn_blocking(move || {
+            let extracted = pack::extract(&path, &partial, &known);
+            let _ = sender.send((path, extracted));
+            ctx.request_repaint();
+        });
+    }
+
+    fn receive_packs(&mut self) {
+        while let Ok((path, extracted)) = self.pack_receiver.try_recv() {
+            match extracted {
+                Ok(extracted) => self.add_pack(extracted),
+                Err(e) => self.notifier.error(Error::Context(
+                    format!("Failed to import {}", path.display()),
+                    Box::new(e),
+                )),
+            }
+        }
+    }
+
+    /// Adds the archives and mods of a pack to the library and offers to restore its active mods
+    fn add_pack(&mut self, extracted: Extracted) {
+        let Extracted { pack, archives } = extracted;
+        for (hash, temp) in archives {
+            let Some(entry) = pack.archives.get(&hash) else {
+                continue;
+            };
+            if let Err(e) = self.store.add_entry(&temp, &hash, entry) {
+                let _ = remove_file(&temp);
+                self.notifier.error(format!(
+                    "Failed to add archive {hash} of {}: {e}",
+                    pack.name
+                ));
+            }
+        }
+        let active_hashes: Vec<(String, Vec<String>)> = pack
+            .active
+            .iter()
+            .map(|m| (m.hash.clone(), m.units.clone()))
+            .collect();
+        let mut added = 0;
+        // Mods whose archive the library has already are left alone, with the ids they have here,
+        // and mods whose archive failed to import are left out
+        for mut r#mod in pack.active.into_iter().chain(pack.inactive) {
+            let known = self
+                .active
+                .iter()
+                .chain(self.inactive.iter())
+                .any(|m| m.hash == r#mod.hash);
+            if known || self.store.entry(&r#mod.hash).is_none() {
+                continue;
+            }
+            if r#mod.mod_id >= HIGH {
+                // Ids of zips added by hand are only unique on the computer they were added on
+                let id = self.next_local_id();
+                (r#mod.mod_id, r#mod.file_id) = (id, id);
+                self.store.add_source(&r#mod.hash, id, id);
+            }
+            r#mod.pending = false;
+            self.add_to_library(r#mod.library_copy());
+            added += 1;
+        }
+        self.notifier.info(format!(
+            "Added {added} mods from the pack {} to the library",
+            pack.name
+        ));
+        let active: Vec<GameMod> = active_hashes
+            .into_iter()
+            .filter_map(|(hash, units)| {
+                let mut r#mod = self
+                    .inactive
+                    .iter()
+                    .chain(self.active.iter())
+                    .find(|m| m.hash == hash)?
+                    .clone();
+                r#mod.units = units;
+                Some(r#mod)
+            })
+            .collect();
+        if active.is_empty() {
+            return;
+        }
+        self.confirmations.push_back(Confirmation {
+            title: format!("Restore {}?", pack.name),
+            message: String::from(
+                "Pick the game folder to set the pack up in next. What would change in its Mods \
+                 folder is listed before anything is touched.",
+            ),
+            confirm: String::from("Choose game folder"),
+            action: Confirm::RestorePack(pack.name, active),
+        });
+    }
+
+    /// Selects or adds the installation for a pack and lists how its mods differ from the pack,
+    /// which is synced like any lockfile once confirmed
+    fn restore_pack(&mut self, name: String, active: Vec<GameMod>) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title(&format!("Game folder for {name}"))
+            .set_directory(&self.game_path)
+            .pick_folder()
+        else {
+            return;
+        };
+        if !game::is_game_path(&path) {
+            self.notifier.error(format!(
+                "{} does not contain {GAME_DLL}, is it the game folder?",
+                path.display()
+            ));
+            return;
+        }
+        if path != self.game_path {
+            let index = match self.installations.iter().position(|i| i.game_path == path) {
+                Some(index) => index,
+                None => {
+                    let mut unique = name.clone();
+                    let mut n = 2;
+                    while self.installation_name_taken(&unique) {
+                        unique = format!("{name} ({n})");
+                        n += 1;
+                    }
+                    let installation = self.new_installation(unique, path);
+                    self.installations.push(installation);
+                    self.installations.len() - 1
+                }
+            };
+            self.switch_installation(index);
+        }
+        let mods = active.iter().map(|m| Locked::new(m, HIGH)).collect();
+        self.verify_lockfile(Lockfile {
+            version: LOCKFILE_VERSION,
+            mods,
+        });
+    }
+
+    fn compare_modlist(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Mod list or lockfile", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
+        match modlist::load(&path) {
+            Ok(list) => {
+                self.comparison = Some(Comparison {
+                    name: path
+                        .file_name()
+                        .map_or_else(String::new, |n| n.to_string_lossy().to_string()),
+                    differences: modlist::compare(&list.mods, &self.active),
+                    theirs: list.mods,
+                });
+            }
+            Err(e) => self.notifier.error(Error::Context(
+                format!("Failed to read {}", path.display()),
+                Box::new(e),
+            )),
+        }
+    }
+
+    /// What can be done on this side about a difference, `None` if nothing
+    fn reconcile_action(&self, difference: &Difference) -> Option<(String, Reconcile)> {
+        let (entry, mine) = match difference {
+            Difference::Extra(mine) => {
+                return Some((String::from("Disable"), Reconcile::Disable(mine.clone())));
+            }
+            Difference::Missing(entry) => (entry, None),
+            Difference::Version { theirs, mine } => (theirs, Some(mine)),
+        };
+        if self.inactive.iter().any(|m| entry.matches(m)) {
+            let label = match mine {
+                None => "Enable",
+                Some(mine) if compare_versions(&entry.version, &mine.version).is_gt() => "Upgrade",
+                Some(_) => "Downgrade",
+            };
+            let action = Reconcile::Enable {
+                entry: entry.clone(),
+                replaces: mine.cloned(),
+            };
+            return Some((String::from(label), action));
+        }
+        if self.premium
+            && let Some(nxm) = entry.nxm()
+        {
+            return Some((String::from("Download"), Reconcile::Download(nxm)));
+        }
+        entry
+            .page()
+            .map(|page| (String::from("Open page"), Reconcile::OpenPage(page)))
+    }
+
+    fn reconcile(&mut self, action: Reconcile) {
+        match action {
+            Reconcile::Enable { entry, replaces } => {
+                if let Some(mine) = replaces
+                    && let Some(index) = self
+                        .active
+                        .iter()
+                        .position(|m| m.hash == mine.hash && m.mod_id == mine.mod_id)
+                {
+                    let mut r#mod = self.active[index].clone();
+                    if let Err(e) = self.switch_active_inactive(&mut r#mod, index, true) {
+                        self.notifier.error(e);
+                        return;
+                    }
+                }
+                if let Some(index) = self.inactive.iter().position(|m| entry.matches(m)) {
+                    let mut r#mod = self.inactive[index].clone();
+                    if let Err(e) = self.switch_active_inactive(&mut r#mod, index, false) {
+                        self.notifier.error(e);
+                    }
+                }
+            }
+            Reconcile::Download(nxm) => self.downloader.start(nxm),
+            Reconcile::OpenPage(page) => {
+                if let Err(e) = launch::open(&page) {
+                    self.notifier.error(format!("Failed to open {page}: {e}"));
+                }
+            }
+            Reconcile::Disable(mine) => {
+                if let Some(index) = self
+                    .active
+                    .iter()
+                    .position(|m| m.hash == mine.hash && m.mod_id == mine.mod_id)
+                {
+                    let mut r#mod = self.active[index].clone();
+                    if let Err(e) = self.switch_active_inactive(&mut r#mod, index, true) {
+                        self.notifier.error(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn comparison_display(&mut self, ctx: &egui::Context) {
+        let Some(comparison) = self.comparison.take() else {
+            return;
+        };
+        let actions: Vec<Option<(String, Reconcile)>> = comparison
+            .differences
+            .iter()
+            .map(|d| self.reconcile_action(d))
+            .collect();
+        let mut open = true;
+        let mut chosen = vec![];
+        let mut apply_all = false;
+        egui::Window::new(format!("Compare with {}", comparison.name))
+            .id(egui::Id::new("comparison"))
+            .open(&mut open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                if comparison.differences.is_empty() {
+                    ui.label("The active mods are the same on both sides.");
+                }
+                for (i, (difference, action)) in
+                    comparison.differences.iter().zip(&actions).enumerate()
+                {
+                    ui.horizontal(|ui| {
+                        match difference {
+                            Difference::Missing(entry) => {
+                                ui.colored_label(egui::Color32::RED, "Missing");
+                                ui.label(format!("{} {}", entry.name, entry.version));
+                            }
+                            Difference::Extra(mine) => {
+                                ui.colored_label(egui::Color32::GOLD, "Only mine");
+                                ui.label(format!("{} {}", mine.name, mine.version));
+                            }
+                            Difference::Version { theirs, mine } => {
+                                ui.colored_label(egui::Color32::GOLD, "Version");
+                                ui.label(format!(
+                                    "{} {}, mine is {}",
+                                    theirs.name, theirs.version, mine.version
+                                ));
+                            }
+                        }
+                        if let Some((label, _)) = action
+                            && ui.button(label).clicked()
+                        {
+                            chosen.push(i);
+                        }
+                    });
+                }
+                ui.separator();
+                let any = actions
+                    .iter()
+                    .flatten()
+                    .any(|(_, a)| !matches!(a, Reconcile::OpenPage(_)));
+                if ui
+                    .add_enabled(any, egui::Button::new("Apply all"))
+                    .on_hover_text("Everything except opening pages")
+                    .clicked()
+                {
+                    chosen = (0..actions.len()).collect();
+                    apply_all = true;
+                }
+            });
+        let acted = !chosen.is_empty();
+        for (i, action) in actions.into_iter().enumerate() {
+            if let Some((_, action)) = action
+                && chosen.contains(&i)
+                && !(apply_all && matches!(action, Reconcile::OpenPage(_)))
+            {
+                self.reconcile(action);
+            }
+        }
+        if !open {
+            return;
+        }
+        let mut comparison = comparison;
+        if acted {
+            comparison.differences = modlist::compare(&comparison.theirs, &self.active);
+        }
+        self.comparison = Some(comparison);
+    }
+
+    fn save_lockfile(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("sdmm.lock.json")
+            .add_filter("Lockfile", &["json"])
+            .save_file()
+        else {
+            return;
+        };
+        let external = self.active.iter().filter(|m| m.external).count();
+        if external > 0 {
+            self.notifier.warning(format!(
+                "{external} mods installed outside of SDMM have no archive and are not locked"
+            ));
+        }
+        match lockfile::save(&path, &self.active, HIGH) {
+            Ok(()) => self
+                .notifier
+                .info(format!("Saved the lockfile to {}", path.display())),
+            Err(e) => self
+                .notifier
+                .error(format!("Failed to save the lockfile: {e}")),
+        }
+    }
+
+    fn pick_lockfile(&mut self) -> Option<Lockfile> {
+        let path = rfd::FileDialog::new()
+            .add_filter("Lockfile", &["json"])
+            .pick_file()?;
+        match lockfile::load(&path) {
+            Ok(lockfile) => Some(lockfile),
+            Err(e) => {
+                self.notifier.error(Error::Context(
+                    format!("Failed to read {}", path.display()),
+                    Box::new(e),
+                ));
+                None
+            }
+        }
+    }
+
+    fn verify_lockfile(&mut self, lockfile: Lockfile) {
+        let mods_path = self.game_path.join("mods");
+        let drift = lockfile::verify(&lockfile, &self.active, &self.inactive, &mods_path);
+        self.lock_report = Some(LockReport { lockfile, drift });
+    }
+
+    fn confirm_sync(&mut self, lockfile: Lockfile) {
+        self.confirmations.push_back(Confirmation {
+            title: String::from("Sync to lockfile?"),
+            message: String::from(
+                "Every active mod that is not in the lockfile gets disabled and the locked \
+                 versions are installed in their place. Changes to the files of locked mods are \
+                 lost.",
+            ),
+            confirm: String::from("Sync"),
+            action: Confirm::SyncLockfile(lockfile),
+        });
+    }
+
+    /// Disables whatever differs from the lockfile and enables the locked archives, downloading
+    /// the ones missing from the library where the account allows it
+    fn sync_lockfile(&mut self, lockfile: Lockfile) {
+        let mods_path = self.game_path.join("mods");
+        let mut index = 0;
+        while index < self.active.len() {
+            let modified =
+                !receipt::modified_files(&mods_path, &self.active[index].installed).is_empty();
+            if !modified && lockfile.mods.iter().any(|l| l.is(&self.active[index])) {
+                index += 1;
+                continue;
+            }
+            let mut r#mod = self.active[index].clone();
+            if let Err(e) = self.switch_confirmed(&mut r#mod, index, true) {
+                self.notifier.error(e);
+                index += 1;
+                continue;
+            }
+            // A stashed copy would bring the changed files back when enabled again
+            if modified
+                && r#mod.stashed
+                && let Some(disabled) = self
+                    .inactive
+                    .iter_mut()
+                    .rev()
+                    .find(|m| m.hash == r#mod.hash)
+            {
+                match disable::discard(&mods_path, &r#mod.folder_list()) {
+                    Ok(()) => disabled.stashed = false,
+                    Err(e) => self.notifier.error(format!(
+                        "Failed to delete disabled copy of {}: {e}",
+                        r#mod.name
+                    )),
+                }
+            }
+        }
+        let mut downloading = 0;
+        for locked in &lockfile.mods {
+            if self.active.iter().any(|m| locked.is(m)) {
+                continue;
+            }
+            if let Some(index) = self.inactive.iter().position(|m| m.hash == locked.hash) {
+                self.inactive[index].units = locked.units.clone();
+                let mut r#mod = self.inactive[index].clone();
+                if let Err(e) = self.switch_confirmed(&mut r#mod, index, false) {
+                    self.notifier.error(e);
+                }
+            } else if let Some(nxm) = locked.entry.nxm()
+                && self.premium
+            {
+                self.downloader.start(nxm);
+                downloading += 1;
+            }
+        }
+        if downloading > 0 {
+            self.notifier.info(format!(
+                "Downloading {downloading} locked mods, sync again once they are done"
+            ));
+        }
+        self.verify_lockfile(lockfile);
+        if self
+            .lock_report
+            .as_ref()
+            .map_or(false, |r| r.drift.is_empty())
+        {
+            self.notifier.info("The active mods match the lockfile");
+        }
+    }
+
+    fn lock_report_display(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.lock_report else {
+            return;
+        };
+        let mut open = true;
+        let mut sync = false;
+        let mut verify = false;
+        egui::Window::new("Lockfile")
+            .id(egui::Id::new("lock-report"))
+            .open(&mut open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                if report.drift.is_empty() {
+                    ui.label("The active mods match the lockfile.");
+                }
+                for drift in &report.drift {
+                    ui.horizontal(|ui| {
+                        match drift {
+                            Drift::Missing(locked) => {
+                                if let Some(page) = locked.entry.page()
+                                    && ui.button("Open page").clicked()
+                                    && let Err(e) = launch::open(&page)
+                                {
+                                    self.notifier.error(format!("Failed to open {page}: {e}"));
+                                }
+                            }
+                            Drift::Modified { .. } | Drift::Different { .. } => {
+                                ui.colored_label(egui::Color32::GOLD, "⚠");
+                            }
+                            _ => {}
+                        }
+                        ui.label(drift.to_string());
+                    });
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!report.drift.is_empty(), egui::Button::new("Sync"))
+                        .clicked()
+                    {
+                        sync = true;
+                    }
+                    if ui.button("Verify again").clicked() {
+                        verify = true;
+                    }
+                });
+            });
+        if !open {
+            self.lock_report = None;
+        } else if sync || verify {
+            let Some(report) = self.lock_report.take() else {
+                return;
+            };
+            if sync {
+                self.confirm_sync(report.lockfile);
+            } else {
+                self.verify_lockfile(report.lockfile);
+            }
+        }
+    }
+
+    fn install_choice_display(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut install = false;
+        if let Some(choice) = self.install_choices.front_mut() {
+            egui::Window::new(format!("Install {}", choice.r#mod.name))
+                .id(egui::Id::new("install-choice"))
+                .open(&mut open)
+                .vscroll(true)
+                .show(ctx, |ui| {
+                    ui.label("This archive contains several mods, pick the ones to install.");
+                    ui.separator();
+                    for (unit, manifest, checked) in choice.units.iter_mut() {
+                        let text = match manifest {
+                            Some(m) => format!("{} ({} {})", unit.folder_name, m.name, m.version),
+                            None => unit.folder_name.clone(),
+                        };
+                        ui.checkbox(checked, text);
+                    }
+                    ui.separator();
+                    let any = choice.units.iter().any(|(_, _, checked)| *checked);
+                    if ui.add_enabled(any, egui::Button::new("Install")).clicked() {
+                        install = true;
+                    }
+                });
+        }
+        if install && let Some(choice) = self.install_choices.pop_front() {
+            let units: Vec<String> = choice
+                .units
+                .iter()
+                .filter(|(_, _, checked)| *checked)
+                .map(|(unit, _, _)| unit.root.display().to_string())
+                .collect();
+            let position = self.inactive.iter().position(|m| {
+                m.zip_name == choice.r#mod.zip_name
+                    && m.mod_id == choice.r#mod.mod_id
+                    && m.file_id == choice.r#mod.file_id
+            });
+            if let Some(index) = position {
+                self.inactive[index].units = units;
+                let mut r#mod = self.inactive[index].clone();
+                if let Err(e) = self.switch_active_inactive(&mut r#mod, index, false) {
+                    self.notifier.error(e);
+                }
+            }
+        }
+        if !open {
+            self.install_choices.pop_front();
+        }
+    }
+
+    fn preview_display(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        if let Some(window) = &self.preview {
+            egui::Window::new(&window.r#mod.name)
+                .id(egui::Id::new("archive-preview"))
+                .open(&mut open)
+                .vscroll(true)
+                .show(ctx, |ui| {
+                    ui.label(&window.r#mod.zip_name);
+                    if let Some(entry) = self.store.entry(&window.r#mod.hash)
+                        && entry.names.len() > 1
+                    {
+                        ui.label(format!("Also added as {}", entry.names.join(", ")));
+                    }
+                    let preview = match &window.preview {
+                        Ok(preview) => preview,
+                        Err(e) => {
+                            ui.label(format!("Failed to read archive: {e}"));
+                            return;
+                        }
+                    };
+                    ui.label(format!(
+                        "Size: {:.2} MB",
+                        preview.total_size as f64 / 1024. / 1024.
+                    ));
+                    if preview.has_code() {
+                        ui.label("Contains SMAPI mods with code (DLL)");
+                    } else {
+                        ui.label("Only contains content packs");
+                    }
+                    if !window.replaces.is_empty() {
+                        ui.separator();
+                        ui.heading("Replaces");
+                        for name in &window.replaces {
+                            ui.label(name);
+                        }
+                    }
+                    ui.separator();
+                    ui.heading("Mods");
+                    for (unit, manifest) in &preview.units {
+                        ui.group(|ui| {
+                            ui.strong(&unit.folder_name);
+                            match manifest {
+                                Some(manifest) => {
+                                    ui.label(format!(
+                                        "{} {} by {}",
+                                        manifest.name, manifest.version, manifest.author
+                                    ));
+                                    ui.label(format!("UniqueID: {}", manifest.unique_id));
+                                    if let Some(pack) = &manifest.content_pack_for {
+                                        ui.label(format!("Content pack for {}", pack.unique_id));
+                                    }
+                                    if let Some(api) = &manifest.minimum_api_version {
+                                        ui.label(format!("Requires SMAPI {api}"));
+                                    }
+                                    if !manifest.description.is_empty() {
+                                        ui.label(&manifest.description);
+                                    }
+                                }
+                                None => {
+                                    ui.label("No manifest.json found");
+                                }
+                            }
+                        });
+                    }
+                    ui.separator();
+                    egui::CollapsingHeader::new(format!("Files ({})", preview.files.len())).show(
+                        ui,
+                        |ui| {
+                            for (name, _) in preview.files.iter().filter(|(_, is_dir)| !is_dir) {
+                                ui.label(name);
+                            }
+                        },
+                    );
+                });
+        }
+        if !open {
+            self.preview = None;
+        }
+    }
+
+    fn browse(&mut self, ctx: &egui::Context) {
+        // TODO: Panel for browsing Mods:
+        //      Some mods will be taken from sources like github if they have releases available.
+        //      Possible that I may build and host versions myself from github to improve
+        //      simplicity.
+        //      All other mods will just require opening the NexusMods page and downloading
+        //          MAYBE: unless a premium user that can oauth using the app and then select
+        //              and download from inside the app.
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Browse");
+            ui.separator();
+            ui.heading("COMING SOON");
+        });
+    }
+
+    fn switch_active_inactive(
+        &mut self,
+        r#mod: &mut GameMod,
+        index: usize,
+        is_active: bool,
+    ) -> Result<(), Error> {
+        self.switch_with(r#mod, index, is_active, self.disable_strategy, false)
+    }
+
+    /// Switches without asking first, for when the user already agreed to losing changed files
+    /// and overwriting other mods
+    fn switch_confirmed(
+        &mut self,
+        r#mod: &mut GameMod,
+        index: usize,
+        is_active: bool,
+    ) -> Result<(), Error> {
+        self.switch_with(r#mod, index, is_active, self.disable_strategy, true)
+    }
+
+    /// Enables or disables a mod, asking for a confirmation instead when changed files would be
+    /// deleted or another mod's files overwritten, unless `confirmed`
+    fn switch_with(
+        &mut self,
+        r#mod: &mut GameMod,
+        index: usize,
+        is_active: bool,
+        strategy: DisableStrategy,
+        confirmed: bool,
+    ) -> Result<(), Error> {
+        if r#mod.mod_id == SMAPI_MOD_ID {
+            return self.switch_smapi(r#mod, index, is_active);
+        }
+        let mods_path = self.game_path.join("mods");
+        if is_active {
+            if strategy == DisableStrategy::Rename || r#mod.external {
+                let folders = r#mod.folder_list();
+                let taken = disable::stashed(&mods_path, &folders);
+                if !taken.is_empty() && !confirmed {
+                    let owners: Vec<&str> = self
+                        .inactive
+                        .iter()
+                        .filter(|m| {
+                            m.stashed && m.folder_list().iter().any(|f| folders.contains(f))
+                        })
+                        .map(|m| m.name.as_str())
+                        .collect();
+                    let owners = if owners.is_empty() {
+                        String::from("unknown mods")
+                    } else {
+                        owners.join(", ")
+                    };
+                    self.confirmations.push_back(Confirmation {
+                        title: format!("Disable {}?", r#mod.name),
+                        message: format!(
+                            "{DISABLED_FOLDER} already holds disabled copies of {owners} under \
+                             the same folder names. They are deleted with any changes made to \
+                             them."
+                        ),
+                        confirm: String::from("Delete and disable"),
+                        action: Confirm::ReplaceStash(Box::new(r#mod.clone())),
+                    });
+                    return Ok(());
+                }
+                disable::stash(&mods_path, &folders)
+                    .context(|| format!("Failed to move {} into {DISABLED_FOLDER}", r#mod.name))?;
+                r#mod.stashed = true;
+            } else if r#mod.installed.is_empty() {
+                // Mods installed before receipts were kept only know their folders
+                for folder in r#mod.folder_list() {
+                    if let Err(e) = remove_dir_all(mods_path.join(&folder)) {
+                        self.notifier
+                            .error(format!("Failed to remove mod folder {}: {}", folder, e));
+                    }
+                }
+            } else {
+                let modified = receipt::modified_files(&mods_path, &r#mod.installed);
+                if !modified.is_empty() && !confirmed {
+                    self.confirm_switch(
+                        r#mod,
+                        true,
+                        "were changed since it was installed, disabling it deletes them",
+                        &modified,
+                    );
+                    return Ok(());
+                }
+                for path in &modified {
+                    warning!(
+                        "Deleting {} of {}, it was changed",
+                        path.display(),
+                        r#mod.name
+                    );
+                }
+                let shared: HashSet<PathBuf> = self
+                    .active
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != index)
+                    .flat_map(|(_, m)| m.installed.iter().map(|f| f.path.clone()))
+                    .collect();
+                if let Err(e) =
+                    receipt::uninstall(&mods_path, &r#mod.installed, &r#mod.folders, &shared)
+                {
+                    self.notifier
+                        .error(format!("Failed to remove mod {}: {}", r#mod.name, e));
+                }
+                r#mod.installed.clear();
+            }
+            self.inactive.push(r#mod.clone());
+            self.active.remove(index);
+            info!("Disabled {} {}", r#mod.name, r#mod.version);
+        } else {
+            if self.active.iter().any(|m| m.mod_id == r#mod.mod_id) {
+                warning!("Mod {} is already active", r#mod.mod_id);
+                return Ok(());
+            }
+            if r#mod.stashed {
+                match disable::restore(&mods_path, &r#mod.folder_list()) {
+                    Ok(()) => {
+                        r#mod.stashed = false;
+                        self.active.push(r#mod.clone());
+                        self.inactive.remove(index);
+                        info!("Restored {} {}", r#mod.name, r#mod.version);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        self.notifier.warning(format!(
+                            "Failed to restore {} from {}: {e}",
+                            r#mod.name, DISABLED_FOLDER
+                        ));
+                        // It is extracted again below, which leaves the disabled copy stale
+                        if !r#mod.external {
+                            match disable::discard(&mods_path, &r#mod.folder_list()) {
+                                Ok(()) => {
+                                    r#mod.stashed = false;
+                                    self.inactive[index].stashed = false;
+                                }
+                                Err(e) => self.notifier.error(format!(
+                                    "Failed to delete disabled copy of {}: {e}",
+                                    r#mod.name
+                                )),
+                            }
+                        }
+                    }
+                }
+            }
+            if r#mod.external {
+                self.notifier.warning(format!(
+                    "{} was installed outside of SDMM and has no archive",
+                    r#mod.name
+                ));
+                return Ok(());
+            }
+            let file = File::open(self.archive_path(r#mod))
+                .context(|| format!("Failed to open the archive of {}", r#mod.name))?;
+            let units = find_mod_units(&file, &r#mod.fallback_name())
+                .context(|| format!("Failed to read mod {}", r#mod.name))?;
+            let units = if r#mod.units.is_empty() {
+                if units.len() > 1 {
+                    self.open_install_choice(r#mod.clone());
+                    return Ok(());
+                }
+                units
+            } else {
+                let chosen: Vec<ModUnit> = units
+                    .into_iter()
+                    .filter(|unit| r#mod.units.contains(&unit.root.display().to_string()))
+                    .collect();
+                // The archive no longer has the units that were picked, so ask again
+                if chosen.is_empty() {
+                    self.open_install_choice(r#mod.clone());
+                    return Ok(());
+                }
+                chosen
+            };
+            if let Ok(planned) = planned_files(&file, &units) {
+                let conflicts: Vec<PathBuf> = self
+                    .active
+                    .iter()
+                    .flat_map(|other| receipt::conflicts(&planned, &other.installed))
+                    .collect();
+                if !conflicts.is_empty() && !confirmed {
+                    self.confirm_switch(
+                        r#mod,
+                        false,
+                        "belong to other active mods, enabling it overwrites them",
+                        &conflicts,
+                    );
+                    return Ok(());
+                }
+                for other in &self.active {
+                    for path in receipt::conflicts(&planned, &other.installed) {
+                        self.notifier.warning(format!(
+                            "{} overwrote {} of {}",
+                            r#mod.name,
+                            path.display(),
+                            other.name
+                        ));
+                    }
+                }
+            }
+            let installed = install_units(&file, &units, &mods_path)
+                .context(|| format!("Failed to install mod {}", r#mod.name))?;
+            r#mod.folder_name = installed.folders.first().cloned().unwrap_or_default();
+            r#mod.installed = match receipt::create(&mods_path, &installed.files) {
+                Ok(receipt) => receipt,
+                Err(e) => {
+                    self.notifier
+                        .warning(format!("Failed to record install of {}: {e}", r#mod.name));
+                    vec![]
+                }
+            };
+            r#mod.unique_ids = installed
+                .folders
+                .iter()
+                .filter_map(|f| read_manifest(&mods_path.join(f)))
+                .map(|m| m.unique_id)
+                .collect();
+            r#mod.folders = installed.folders;
+            self.active.push(r#mod.clone());
+            self.inactive.remove(index);
+            info!("Installed {} {}", r#mod.name, r#mod.version);
+            self.check_api_versions();
+            if let Some(enabled) = self.active.last()
+                && let (Some(required), Some(smapi)) = (&enabled.needs_api, &self.versions.smapi)
+            {
+                self.notifier.warning(format!(
+                    "{} needs SMAPI {required} but SMAPI {smapi} is installed",
+                    enabled.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn confirm_switch(
+        &mut self,
+        r#mod: &GameMod,
+        is_active: bool,
+        reason: &str,
+        files: &[PathBuf],
+    ) {
+        const SHOWN: usize = 5;
+        let mut message = format!("These files {reason}:");
+        for path in files.iter().take(SHOWN) {
+            message.push_str(&format!("\n{}", path.display()));
+        }
+        if files.len() > SHOWN {
+            message.push_str(&format!("\nand {} more", files.len() - SHOWN));
+        }
+        let action = if is_active { "Disable" } else { "Enable" };
+        self.confirmations.push_back(Confirmation {
+            title: format!("{action} {}?", r#mod.name),
+            message,
+            confirm: String::from(action),
+            action: Confirm::Switch {
+                r#mod: Box::new(r#mod.clone()),
+                is_active,
+            },
+        });
+    }
+
+    fn switch_smapi(
+        &mut self,
+        r#mod: &mut GameMod,
+        index: usize,
+        is_active: bool,
+    ) -> Result<(), Error> {
+        if is_active {
+            if r#mod.installed.is_empty() {
+                // Installed before receipts were kept, the installer archive knows what it wrote
+                File::open(self.archive_path(r#mod))
+                    .map_err(Error::from)
+                    .and_then(|file| smapi::uninstall_legacy(&file, &self.game_path))
+            } else {
+                smapi::uninstall(&self.game_path, &r#mod.installed)
+            }
+            .context(|| format!("Failed to uninstall SMAPI {}", r#mod.version))?;
+            // Older versions extracted the whole installer into the game folder
+            if !r#mod.folder_name.is_empty()
+                && let Err(e) = remove_dir_all(self.game_path.join(&r#mod.folder_name))
+                && e.kind() != io::ErrorKind::NotFound
+            {
+                self.notifier
+                    .error(format!("Failed to remove {}: {e}", r#mod.folder_name));
+            }
+            self.notifier
+                .info(format!("Uninstalled SMAPI {}", r#mod.version));
+            r#mod.installed.clear();
+            r#mod.folder_name.clear();
+            r#mod.folders.clear();
+            self.inactive.push(r#mod.clone());
+            self.active.remove(index);
+        } else {
+            if self.active.iter().any(|m| m.mod_id == SMAPI_MOD_ID) {
+                self.notifier
+                    .warning("Another version of SMAPI is already installed");
+                return Ok(());
+            }
+            r#mod.installed = File::open(self.archive_path(r#mod))
+                .map_err(Error::from)
+                .and_then(|file| smapi::install(&file, &self.game_path))
+                .context(|| format!("Failed to install SMAPI {}", r#mod.version))?;
+            self.notifier
+                .info(format!("Installed SMAPI {}", r#mod.version));
+            self.active.push(r#mod.clone());
+            self.inactive.remove(index);
+        }
+        self.refresh_versions();
+        Ok(())
+    }
+
+    fn play(&mut self, ctx: &egui::Context) {
+        match launch::launch(&self.game_path, self.launch_method, &self.launch_args, ctx) {
+            Ok(game) => {
+                if game.tracked() {
+                    self.state = Menus::Console;
+                }
+                self.game = Some(game);
+            }
+            Err(e) => self
+                .notifier
+                .error(format!("Failed to launch the game: {e}")),
+        }
+    }
+
+    fn console_display(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Console");
+            ui.separator();
+            let Some(game) = &self.game else {
+                ui.label("Press Play to start the game through SMAPI.");
+                return;
+            };
+            if !game.tracked() {
+                ui.label("The game was started through Steam, its console is not available here.");
+                return;
+            }
+            match game.exited {
+                None => ui.label("Running"),
+                Some(Some(0)) => ui.label("The game has exited"),
+                Some(Some(code)) => ui.colored_label(
+                    egui::Color32::RED,
+                    format!("The game exited with code {code}"),
+                ),
+                Some(None) => ui.colored_label(egui::Color32::RED, "The game was terminated"),
+            };
+            ui.separator();
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::both().stick_to_bottom().show_rows(
+                ui,
+                row_height,
+                game.output.len(),
+                |ui, range| {
+                    for line in game.output.range(range) {
+                        ui.add(egui::Label::new(egui::RichText::new(line).monospace()).wrap(false));
+                    }
+                },
+            );
+        });
+    }
+
+    fn load_smapi_log(&mut self) {
+        let Some(path) = latest_log_path() else {
+            self.log_view = Some(Err("Could not find the SMAPI log folder".into()));
+            return;
+        };
+        let log = match SmapiLog::load(&path) {
+            Ok(log) => log,
+            Err(e) => {
+                self.log_view = Some(Err(format!("Failed to read {}: {e}", path.display())));
+                return;
+            }
+        };
+        // SMAPI names mods after their manifest, which can differ from the NexusMods name
+        let mods_path = self.game_path.join("mods");
+        let names: Vec<(Vec<String>, (u64, u64))> = self
+            .active
+            .iter()
+            .map(|m| {
+                let mut names = vec![m.name.to_lowercase()];
+                for folder in m.folder_list() {
+                    if let Some(manifest) = read_manifest(&mods_path.join(folder)) {
+                        names.push(manifest.name.to_lowercase());
+                    }
+                }
+                (names, (m.mod_id, m.file_id))
+            })
+            .collect();
+        let links = log
+            .problems
+            .iter()
+            .map(|problem| {
+                let name = problem.mod_name.to_lowercase();
+                names
+                    .iter()
+                    .find(|(names, _)| names.contains(&name))
+                    .map(|(_, key)| *key)
+            })
+            .collect();
+        self.log_view = Some(Ok(LogView {
+            log,
+            links,
+            level: LogLevel::Trace,
+            source: None,
+        }));
+    }
+
+    fn logs_display(&mut self, ctx: &egui::Context) {
+        let mut disable: Option<(u64, u64)> = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("SMAPI Log");
+                if ui.button("Reload").clicked() {
+                    self.load_smapi_log();
+                }
+            });
+            ui.separator();
+            let view = match &mut self.log_view {
+                Some(Ok(view)) => view,
+                Some(Err(e)) => {
+                    ui.label(e.as_str());
+                    return;
+                }
+                None => return,
+            };
+            ui.label(view.log.path.display().to_string());
+            egui::CollapsingHeader::new(format!("Problems ({})", view.log.problems.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_source("log-problems")
+                        .max_height(200.)
+                        .show(ui, |ui| {
+                            for (problem, link) in view.log.problems.iter().zip(&view.links) {
+                                ui.horizontal(|ui| {
+                                    let (color, kind) = match problem.kind {
+                                        ProblemKind::Skipped => (egui::Color32::RED, "Skipped"),
+                                        ProblemKind::MissingDependency => {
+                                            (egui::Color32::RED, "Missing dependency")
+                                        }
+                                        ProblemKind::Outdated => (egui::Color32::GOLD, "Outdated"),
+                                        ProblemKind::Error => (egui::Color32::RED, "Error"),
+                                    };
+                                    ui.colored_label(color, kind);
+                                    ui.strong(&problem.mod_name);
+                                    ui.label(&problem.detail);
+                                    if let Some(url) = &problem.link
+                                        && ui.button("Update").clicked()
+                                        && let Err(e) = launch::open(url)
+                                    {
+                                        self.notifier.error(format!("Failed to open {url}: {e}"));
+                                    }
+                                    for id in &problem.missing {
+                                        if ui.button(format!("Find {id}")).clicked() {
+                                            let url = format!(
+                                                "https://www.nexusmods.com/stardewvalley/search/?gsearch={id}"
+                                            );
+                                            if let Err(e) = launch::open(&url) {
+                                                self.notifier.error(format!("Failed to open {url}: {e}"));
+                                            }
+                                        }
+                                    }
+                                    if let Some(key) = link
+                                        && problem.kind != ProblemKind::Outdated
+                                        && ui.button("Disable").clicked()
+                                    {
+                                        disable = Some(*key);
+                                    }
+                                });
+                            }
+                        });
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Level")
+                    .selected_text(view.level.name())
+                    .show_ui(ui, |ui| {
+                        for level in LogLevel::ALL {
+                            ui.selectable_value(&mut view.level, level, level.name());
+                        }
+                    });
+                let sources = view.log.sources();
+                egui::ComboBox::from_label("Mod")
+                    .selected_text(view.source.as_deref().unwrap_or("All"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut view.source, None, "All");
+                        for source in sources {
+                            ui.selectable_value(
+                                &mut view.source,
+                                Some(source.to_string()),
+                                source,
+                            );
+                        }
+                    });
+            });
+            let lines: Vec<(LogLevel, String)> = view
+                .log
+                .entries
+                .iter()
+                .filter(|e| e.level >= view.level)
+                .filter(|e| view.source.as_ref().map_or(true, |s| *s == e.source))
+                .flat_map(|e| {
+                    let mut lines = e.message.lines();
+                    let first = format!(
+                        "[{} {} {}] {}",
+                        e.time,
+                        e.level.name(),
+                        e.source,
+                        lines.next().unwrap_or_default()
+                    );
+                    std::iter::once((e.level, first))
+                        .chain(lines.map(|l| (e.level, l.to_string())))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::both()
+                .id_source("log-entries")
+                .show_rows(ui, row_height, lines.len(), |ui, range| {
+                    for (level, line) in &lines[range] {
+                        let color = match level {
+                            LogLevel::Trace | LogLevel::Debug => egui::Color32::GRAY,
+                            LogLevel::Info => ui.visuals().text_color(),
+                            LogLevel::Alert => egui::Color32::LIGHT_BLUE,
+                            LogLevel::Warn => egui::Color32::GOLD,
+                            LogLevel::Error => egui::Color32::RED,
+                        };
+                        let text = egui::RichText::new(line).monospace().color(color);
+                        ui.add(egui::Label::new(text).wrap(false));
+                    }
+                });
+        });
+        if let Some((mod_id, file_id)) = disable
+            && let Some(index) = self
+                .active
+                .iter()
+                .position(|m| m.mod_id == mod_id && m.file_id == file_id)
+        {
+            let mut r#mod = self.active[index].clone();
+            if let Err(e) = self.switch_active_inactive(&mut r#mod, index, true) {
+                self.notifier.error(e);
+            }
+        }
+    }
+
+    fn start_bisect(&mut self) {
+        let mods_path = self.game_path.join("mods");
+        let active: Vec<ModKey> = self
+            .active
+            .iter()
+            .filter(|m| m.mod_id != SMAPI_MOD_ID)
+            .map(|m| (m.mod_id, m.file_id))
+            .collect();
+        if active.len() < 2 {
+            self.notifier
+                .warning("At least two mods need to be active to search for a problem mod");
+            return;
+        }
+        let owners: HashMap<String, ModKey> = self
+            .active
+            .iter()
+            .flat_map(|m| {
+                m.unique_ids
+                    .iter()
+                    .map(|id| (id.to_lowercase(), (m.mod_id, m.file_id)))
+            })
+            .collect();
+        let mut dependencies: HashMap<ModKey, Vec<ModKey>> = HashMap::new();
+        for r#mod in &self.active {
+            let key = (r#mod.mod_id, r#mod.file_id);
+            for folder in r#mod.folder_list() {
+                let Some(manifest) = read_manifest(&mods_path.join(folder)) else {
+                    continue;
+                };
+                let required = manifest
+                    .dependencies
+                    .iter()
+                    .filter(|d| d.is_required)
+                    .map(|d| d.unique_id.to_lowercase())
+                    .chain(
+                        manifest
+                            .content_pack_for
+                            .map(|p| p.unique_id.to_lowercase()),
+                    );
+                for id in required {
+                    if let Some(owner) = owners.get(&id)
+                        && *owner != key
+                    {
+                        dependencies.entry(key).or_default().push(*owner);
+                    }
+                }
+            }
+        }
+        let bisect = Bisect::start(active, dependencies);
+        self.apply_active_set(&bisect.wanted(), &bisect.original);
+        self.bisect = Some(bisect);
+    }
+
+    /// Enables the `wanted` mods out of `candidates` and disables the rest of them. Mods are
+    /// always renamed, which keeps toggling instant and leaves every config in place.
+    fn apply_active_set(&mut self, wanted: &[ModKey], candidates: &[ModKey]) {
+        for key in candidates {
+            let enable = wanted.contains(key);
+            let list = if enable { &self.inactive } else { &self.active };
+            if let Some(index) = list.iter().position(|m| (m.mod_id, m.file_id) == *key) {
+                let mut r#mod = list[index].clone();
+                let result =
+                    self.switch_with(&mut r#mod, index, !enable, DisableStrategy::Rename, true);
+                if let Err(e) = result {
+                    self.notifier.error(e);
+                }
+            }
+        }
+    }
+
+    fn end_bisect(&mut self, disable: &[ModKey]) {
+        if let Some(bisect) = self.bisect.take() {
+            let wanted: Vec<ModKey> = bisect
+                .original
+                .iter()
+                .filter(|key| !disable.contains(key))
+                .copied()
+                .collect();
+            self.apply_active_set(&wanted, &bisect.original);
+        }
+    }
+
+    fn mod_name(&self, key: &ModKey) -> String {
+        self.active
+            .iter()
+            .chain(self.inactive.iter())
+            .find(|m| (m.mod_id, m.file_id) == *key)
+            .map(|m| m.name.clone())
+            .unwrap_or_else(|| format!("{key:?}"))
+    }
+
+    fn bisect_display(&mut self, ctx: &egui::Context) {
+        let Some(bisect) = &self.bisect else {
+            return;
+        };
+        let mut answer: Option<bool> = None;
+        let mut end: Option<Vec<ModKey>> = None;
+        let mut play = false;
+        let suspects: Vec<String> = bisect.suspects.iter().map(|k| self.mod_name(k)).collect();
+        let running = self.game.as_ref().map_or(false, |g| g.running());
+        egui::Window::new("Find problem mod")
+            .id(egui::Id::new("bisect"))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if bisect.finished {
+                    match suspects.as_slice() {
+                        [] if bisect.without_mods => {
+                            ui.label(
+                                "The problem also happens without any mods, no mod causes it.",
+                            );
+                        }
+                        [] => {
+                            ui.label("The problem went away, it may need several mods together.");
+                        }
+                        [culprit] => {
+                            ui.label("The problem is caused by");
+                            ui.strong(culprit);
+                        }
+                        _ => {
+                            ui.label("These mods depend on each other, one of them is the cause:");
+                            for name in &suspects {
+                                ui.strong(name);
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if !bisect.suspects.is_empty()
+                            && ui.button("Restore and disable them").clicked()
+                        {
+                            end = Some(bisect.suspects.clone());
+                        }
+                        if ui.button("Restore all").clicked() {
+                            end = Some(vec![]);
+                        }
+                    });
+                    return;
+                }
+                ui.label(match bisect.check {
+                    Check::Half => format!(
+                        "Step {}: {} suspects left, {} of them are enabled.",
+                        bisect.step + 1,
+                        bisect.suspects.len(),
+                        bisect.testing.len()
+                    ),
+                    Check::WithoutMods => format!(
+                        "Step {}: every mod is disabled to make sure one of them is the cause.",
+                        bisect.step + 1
+                    ),
+                    Check::Culprit => format!(
+                        "Step {}: only {} and what they need are enabled to confirm the cause.",
+                        bisect.step + 1,
+                        suspects.join(", ")
+                    ),
+                });
+                ui.label("Start the game and check whether the problem still happens.");
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!running, egui::Button::new("▶ Play"))
+                        .clicked()
+                    {
+                        play = true;
+                    }
+                    if ui.button("Still broken").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("Works now").clicked() {
+                        answer = Some(false);
+                    }
+                    if ui.button("Abort").clicked() {
+                        end = Some(vec![]);
+                    }
+                });
+            });
+        if play {
+            self.play(ctx);
+        }
+        if let Some(still_broken) = answer
+            && let Some(bisect) = &mut self.bisect
+        {
+            bisect.answer(still_broken);
+            let (wanted, original) = (bisect.wanted(), bisect.original.clone());
+            if !bisect.finished {
+                self.apply_active_set(&wanted, &original);
+            }
+        }
+        if let Some(disable) = end {
+            self.end_bisect(&disable);
+        }
+    }
+
+    fn reset_settings_edit(&mut self) {
+        self.settings_edit = SettingsEdit {
+            game_path: self.game_path.display().to_string(),
+            download_path: self.download_path.display().to_string(),
+            api_key: self.api_key.clone(),
+            installation_name: self.installation_name.clone(),
+            ..Default::default()
+        };
+    }
+
+    fn set_game_path(&mut self, path: PathBuf) {
+        self.game_path = path;
+        self.settings_edit.game_path = self.game_path.display().to_string();
+        self.scan_mods_folder();
+        self.refresh_versions();
+        self.log_view = None;
+    }
+
+    fn set_download_path(&mut self, path: PathBuf) -> io::Result<()> {
+        self.store.relocate(&path)?;
+        self.download_path = path;
+        self.download_settings.write().unwrap().download_path = self.download_path.clone();
+        Ok(())
+    }
+
+    fn set_api_key(&mut self, key: String) {
+        self.api_key = key;
+        self.needs_key = self.api_key.is_empty();
+        self.download_settings.write().unwrap().api_key = self.api_key.clone();
+        self.fetch_missing_metadata();
+        self.premium = false;
+        if !self.api_key.is_empty() {
+            self.metadata.account();
+        }
+    }
+
+    /// Starts checking NexusMods for newer versions of the downloaded mods in the background
+    fn check_updates(&mut self) {
+        if self.checking_updates || self.api_key.is_empty() {
+            return;
+        }
+        let mut mod_ids: Vec<u64> = self
+            .library()
+            .filter(|m| m.mod_id != 0 && m.mod_id < HIGH && !m.external)
+            .map(|m| m.mod_id)
+            .collect();
+        mod_ids.sort_unstable();
+        mod_ids.dedup();
+        self.metadata.latest_versions(mod_ids);
+        self.checking_updates = true;
+        self.checked_updates = true;
+        self.last_update_check = unix_time();
+    }
+
+    /// Requests what is still unknown about mods from an earlier session
+    fn fetch_missing_metadata(&mut self) {
+        if self.api_key.is_empty() {
+            return;
+        }
+        let metadata = self.metadata.clone();
+        let mut requested: Vec<(u64, u64)> = vec![];
+        for r#mod in self.library_mut() {
+            if r#mod.mod_id == 0 || r#mod.mod_id >= HIGH || r#mod.external {
+                continue;
+            }
+            let key = (r#mod.mod_id, r#mod.file_id);
+            if r#mod.file_id == 0 {
+                r#mod.pending = true;
+                if !requested.contains(&key) {
+                    metadata.file_id(r#mod.mod_id, r#mod.zip_name.clone(), r#mod.version.clone());
+                }
+            } else if r#mod.pending || r#mod.placeholder() {
+                r#mod.pending = true;
+                if !requested.contains(&key) {
+                    metadata.details(r#mod.mod_id, r#mod.file_id);
+                }
+            }
+            requested.push(key);
+        }
+    }
+
+    fn receive_metadata(&mut self) {
+        while let Ok(metadata) = self.metadata_receiver.try_recv() {
+            match metadata {
+                Metadata::FileId {
+                    mod_id,
+                    zip_name,
+                    file_id,
+                } => {
+                    let mut sources: Vec<String> = vec![];
+                    for r#mod in self.library_mut() {
+                        if r#mod.mod_id == mod_id
+                            && r#mod.zip_name == zip_name
+                            && r#mod.file_id == 0
+                        {
+                            r#mod.pending = false;
+                            if let Some(file_id) = file_id {
+                                r#mod.file_id = file_id;
+                                sources.push(r#mod.hash.clone());
+                            }
+                        }
+                    }
+                    if let Some(file_id) = file_id {
+                        for hash in sources.iter().filter(|h| !h.is_empty()) {
+                            self.store.add_source(hash, mod_id, file_id);
+                        }
+                    }
+                }
+                Metadata::Details {
+                    mod_id,
+                    file_id,
+                    details,
+                } => {
+                    for r#mod in self.library_mut() {
+                        if (r#mod.mod_id, r#mod.file_id) != (mod_id, file_id) || !r#mod.pending {
+                            continue;
+                        }
+                        r#mod.pending = false;
+                        if let Ok(found) = &details {
+                            let (mod_details, file_details) = &**found;
+                            r#mod.name = mod_details.name.clone();
+                            r#mod.author = mod_details.author.clone();
+                            r#mod.version = file_details
+                                .version
+                                .clone()
+                                .unwrap_or_else(|| String::from("0"));
+                        }
+                    }
+                    if let Err(e) = details {
+                        self.notifier.error(e);
+                    }
+                    // The same version may already be in the library from another archive
+                    if let Some(index) = self
+                        .inactive
+                        .iter()
+                        .position(|m| (m.mod_id, m.file_id) == (mod_id, file_id))
+                    {
+                        let version = &self.inactive[index].version;
+                        let duplicate = self.active.iter().chain(self.inactive.iter()).any(|m| {
+                            m.mod_id == mod_id && m.file_id != file_id && &m.version == version
+                        });
+                        if duplicate {
+                            let removed = self.inactive.remove(index);
+                            // Also drop the copies `add_to_library` gave the other installations
+                            for installation in &mut self.installations {
+                                installation.inactive.retain(|m| {
+                                    (m.mod_id, m.file_id) != (mod_id, file_id) || m.stashed
+                                });
+                            }
+                            if !removed.hash.is_empty()
+                                && !self.library().any(|m| m.hash == removed.hash)
+                                && let Err(e) =
+                                    self.store.remove_source(&removed.hash, mod_id, file_id)
+                            {
+                                self.notifier.error(format!(
+                                    "Failed to delete the archive of {}: {e}",
+                                    removed.name
+                                ));
+                            }
+                        }
+                    }
+                }
+                Metadata::Account { premium } => self.premium = premium,
+                Metadata::LatestVersions(versions) => {
+                    self.checking_updates = false;
+                    for (mod_id, version) in versions {
+                        for r#mod in self.library_mut() {
+                            if r#mod.mod_id == mod_id {
+                                r#mod.latest_version = Some(version.clone());
+                            }
+                        }
+                    }
                 }
             }
-            self.active.push(r#mod.clone());
-            self.inactive.remove(index);
         }
     }
 
     fn settings_display(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Settings");
-            ui.separator();
-            if ui.button("Reset protocol location").clicked() {
-                crate::setup(true).unwrap();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("Settings");
+                ui.separator();
+                if ui.button("Reset protocol location").clicked() {
+                    if let Err(e) = crate::setup(true)
+                        .context(|| String::from("Failed to register the nxm protocol"))
+                    {
+                        self.notifier.error(e);
+                    }
+                }
+                ui.separator();
+                self.installations_settings(ui);
+                ui.separator();
+                self.paths_settings(ui);
+                ui.separator();
+                ui.label("NexusMods API key");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.settings_edit.api_key).password(true),
+                    );
+                    if ui
+                        .add_enabled(
+                            self.settings_edit.api_key != self.api_key,
+                            egui::Button::new("Apply"),
+                        )
+                        .clicked()
+                    {
+                        self.set_api_key(self.settings_edit.api_key.trim().to_string());
+                    }
+                });
+                ui.hyperlink_to(
+                    "Can be found at the bottom of this page",
+                    "https://www.nexusmods.com/users/myaccount?tab=api+access",
+                );
+                ui.separator();
+                ui.label("Theme");
+                ui.horizontal(|ui| {
+                    let dark = ui.radio_value(&mut self.dark_mode, true, "Dark").changed();
+                    let light = ui
+                        .radio_value(&mut self.dark_mode, false, "Light")
+                        .changed();
+                    if dark || light {
+                        ctx.set_visuals(if self.dark_mode {
+                            egui::Visuals::dark()
+                        } else {
+                            egui::Visuals::light()
+                        });
+                    }
+                });
+                ui.separator();
+                ui.label("When disabling a mod");
+                ui.radio_value(
+                    &mut self.disable_strategy,
+                    DisableStrategy::Delete,
+                    "Delete its files, enabling extracts the archive again",
+                );
+                ui.radio_value(
+                    &mut self.disable_strategy,
+                    DisableStrategy::Rename,
+                    format!("Move it into Mods/{DISABLED_FOLDER}, keeping its config"),
+                );
+                ui.separator();
+                ui.label("Check for mod updates");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.update_check, UpdateCheck::Never, "Never");
+                    ui.radio_value(&mut self.update_check, UpdateCheck::OnStartup, "On startup");
+                    ui.radio_value(&mut self.update_check, UpdateCheck::Daily, "Daily");
+                    ui.radio_value(&mut self.update_check, UpdateCheck::Weekly, "Weekly");
+                    if ui
+                        .add_enabled(!self.checking_updates, egui::Button::new("Check now"))
+                        .clicked()
+                    {
+                        self.check_updates();
+                    }
+                    if self.checking_updates {
+                        ui.spinner();
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Simultaneous downloads");
+                    if ui
+                        .add(egui::Slider::new(&mut self.download_concurrency, 1..=8))
+                        .changed()
+                    {
+                        self.download_settings.write().unwrap().concurrency =
+                            self.download_concurrency;
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Log detail");
+                    let before = self.log_level;
+                    for (level, name) in [
+                        (logging::Level::Error, "Errors"),
+                        (logging::Level::Warning, "Warnings"),
+                        (logging::Level::Info, "Info"),
+                        (logging::Level::Debug, "Debug"),
+                    ] {
+                        ui.radio_value(&mut self.log_level, level, name);
+                    }
+                    if self.log_level != before {
+                        logging::set_level(self.log_level);
+                    }
+                    if ui.button("Open log folder").clicked()
+                        && let Some(dir) = logging::log_dir()
+                        && let Err(e) = launch::open(&dir.display().to_string())
+                    {
+                        self.notifier
+                            .error(format!("Failed to open {}: {e}", dir.display()));
+                    }
+                });
+                if ui
+                    .button("Export diagnostics")
+                    .on_hover_text(
+                        "Saves the state without the API key, mod list and logs as a zip",
+                    )
+                    .clicked()
+                {
+                    self.export_diagnostics();
+                }
+                ui.separator();
+                ui.label("Play");
+                ui.radio_value(
+                    &mut self.launch_method,
+                    LaunchMethod::Direct,
+                    "Start SMAPI from the game folder",
+                );
+                ui.radio_value(
+                    &mut self.launch_method,
+                    LaunchMethod::Steam,
+                    "Start through Steam",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Arguments");
+                    ui.add_enabled(
+                        self.launch_method == LaunchMethod::Direct,
+                        egui::TextEdit::singleline(&mut self.launch_args),
+                    );
+                });
+            });
+        });
+    }
+
+    /// An installation with the whole library disabled
+    fn new_installation(&self, name: String, game_path: PathBuf) -> Installation {
+        let mut inactive: Vec<GameMod> = vec![];
+        for r#mod in self.library() {
+            let known = inactive
+                .iter()
+                .any(|m| (m.mod_id, m.file_id) == (r#mod.mod_id, r#mod.file_id));
+            if !r#mod.external && !known {
+                inactive.push(r#mod.library_copy());
+            }
+        }
+        Installation {
+            name,
+            game_path,
+            active: vec![],
+            inactive,
+        }
+    }
+
+    fn installation_name_taken(&self, name: &str) -> bool {
+        self.installation_name == name || self.installations.iter().any(|i| i.name == name)
+    }
+
+    fn installations_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Installations");
+        let name = self.settings_edit.installation_name.trim().to_string();
+        let can_rename = !name.is_empty() && !self.installation_name_taken(&name);
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.settings_edit.installation_name);
+            if ui
+                .add_enabled(can_rename, egui::Button::new("Rename"))
+                .clicked()
+            {
+                self.installation_name = name;
+            }
+        });
+        let mut removed: Option<usize> = None;
+        for (i, installation) in self.installations.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(&installation.name);
+                ui.weak(installation.game_path.display().to_string());
+                if ui
+                    .button("Forget")
+                    .on_hover_text("Removes the installation from SDMM, its files are kept")
+                    .clicked()
+                {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            let name = self.installations[index].name.clone();
+            self.confirmations.push_back(Confirmation {
+                title: format!("Forget {name}?"),
+                message: String::from("Its mod lists are lost, the game folder is left as it is."),
+                confirm: String::from("Forget"),
+                action: Confirm::ForgetInstallation(name),
+            });
+        }
+
+        let name = self.settings_edit.new_installation_name.trim().to_string();
+        let path = PathBuf::from(self.settings_edit.new_installation_path.trim());
+        let valid =
+            !name.is_empty() && !self.installation_name_taken(&name) && game::is_game_path(&path);
+        let mut add = false;
+        ui.horizontal(|ui| {
+            let edit = &mut self.settings_edit;
+            ui.add(egui::TextEdit::singleline(&mut edit.new_installation_name).hint_text("Name"));
+            ui.add(
+                egui::TextEdit::singleline(&mut edit.new_installation_path)
+                    .hint_text("Game folder"),
+            );
+            if ui.button("Browse").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .set_title("Stardew Valley Game Directory")
+                    .pick_folder()
+            {
+                edit.new_installation_path = path.display().to_string();
+            }
+            add = ui
+                .add_enabled(valid, egui::Button::new("Add installation"))
+                .on_disabled_hover_text(format!(
+                    "Needs an unused name and a folder containing {GAME_DLL}"
+                ))
+                .clicked();
+        });
+        if add {
+            let installation = self.new_installation(name, path);
+            self.installations.push(installation);
+            self.settings_edit.new_installation_name.clear();
+            self.settings_edit.new_installation_path.clear();
+        }
+    }
+
+    fn paths_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Game folder");
+        let game_path = PathBuf::from(self.settings_edit.game_path.trim());
+        let valid = game::is_game_path(&game_path);
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.settings_edit.game_path);
+            if ui.button("Browse").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .set_title("Stardew Valley Game Directory")
+                    .set_directory(&self.game_path)
+                    .pick_folder()
+            {
+                self.settings_edit.game_path = path.display().to_string();
+            }
+            if ui.button("Detect").clicked() {
+                match game::detect_game_path() {
+                    Some(path) => self.settings_edit.game_path = path.display().to_string(),
+                    None => {
+                        self.settings_edit.error =
+                            Some("No Steam or GOG install of the game was found".into())
+                    }
+                }
+            }
+            if ui
+                .add_enabled(
+                    valid && game_path != self.game_path,
+                    egui::Button::new("Apply"),
+                )
+                .clicked()
+            {
+                self.set_game_path(game_path);
+            }
+        });
+        if !valid {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!("{GAME_DLL} was not found in this folder"),
+            );
+        }
+
+        ui.label("Archive folder");
+        let download_path = PathBuf::from(self.settings_edit.download_path.trim());
+        // Partial downloads are written to the old folder and would be lost
+        let downloading = self.downloads.values().any(|d| !d.4);
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.settings_edit.download_path);
+            if ui.button("Browse").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .set_title("Archive Directory")
+                    .set_directory(&self.download_path)
+                    .pick_folder()
+            {
+                self.settings_edit.download_path = path.display().to_string();
+            }
+            if ui
+                .add_enabled(
+                    !downloading
+                        && !download_path.as_os_str().is_empty()
+                        && download_path != self.download_path,
+                    egui::Button::new("Move archives"),
+                )
+                .on_disabled_hover_text("Wait for the running downloads to finish")
+                .clicked()
+            {
+                self.settings_edit.error = self
+                    .set_download_path(download_path)
+                    .err()
+                    .map(|e| format!("Failed to move the archives: {e}"));
             }
         });
+        if let Some(error) = &self.settings_edit.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
     }
 
     fn handle_drag_drop(&mut self, ctx: &egui::Context) {
-        let files = &ctx.input().raw.dropped_files;
-        for f in files {
-            let file_path = f.path.clone().unwrap();
-            if let Some(ext) = file_path.extension() && ext != "zip" {
+        // Notifications repaint the context, which deadlocks while its input is borrowed
+        let files: Vec<PathBuf> = ctx
+            .input()
+            .raw
+            .dropped_files
+            .iter()
+            .filter_map(|f| f.path.clone())
+            .collect();
+        for file_path in files {
+            if let Some(ext) = file_path.extension()
+                && ext == PACK_EXTENSION
+            {
+                self.import_pack(file_path, ctx);
                 continue;
             }
-            let file_name = file_path.iter().last().unwrap().to_str().unwrap().to_string();
-            if let Ok(mut ff) = File::open(&f.path.clone().unwrap()) {
-                match File::create(self.download_path.join(&file_name)) {
-                    Ok(ref mut file) => {
-                        let mut bytes = vec![];
-                        ff.read_to_end(&mut bytes).expect(&format!("Failed to read bytes from {}", file_path.display()));
-                        if let Err(e) = file.write_all(&bytes) {
-                            eprintln!("Failed to write bytes to file {} at {}: {e}", file_name, self.download_path.display())
-                        } else {
-                            let mut id = HIGH;
-                            self.inactive.iter().for_each(|m| {
-                                if m.mod_id >= HIGH && m.mod_id <= id{
-                                    id += 1;
-                                }
-                            });
-                            self.active.iter().for_each(|m| {
-                                if m.mod_id >= HIGH && m.mod_id <= id{
-                                    id += 1;
-                                }
-                            });
-                            self.inactive.push(GameMod {
-                                name: file_name.clone(),
-                                zip_name: file_name.clone(),
-                                mod_id: id,
-                                file_id: id,
-                                ..Default::default()
-                            });
-                        }
-                    },
-                    Err(e) => eprintln!("Failed to create file {} at {}: {e}", file_name, self.download_path.display()),
+            if let Some(ext) = file_path.extension()
+                && ext != "zip"
+            {
+                continue;
+            }
+            let Some(file_name) = file_path.file_name() else {
+                continue;
+            };
+            let file_name = file_name.to_string_lossy().to_string();
+            let hash = match receipt::hash_file(&file_path) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    self.notifier.error(Error::Context(
+                        format!("Failed to read {file_name}"),
+                        Box::new(e.into()),
+                    ));
+                    continue;
+                }
+            };
+            if self
+                .inactive
+                .iter()
+                .chain(self.active.iter())
+                .any(|m| m.hash == hash)
+            {
+                self.notifier
+                    .info(format!("{} is already in the library", file_name));
+                continue;
+            }
+            // Another installation may have it, or the store may know it from an earlier drop
+            let known = self
+                .library()
+                .find(|m| m.hash == hash)
+                .map(GameMod::library_copy);
+            if let Some(known) = known {
+                self.add_to_library(known);
+                continue;
+            }
+            let stored_id = self
+                .store
+                .entry(&hash)
+                .and_then(|e| e.sources.iter().find(|s| s.mod_id >= HIGH))
+                .map(|s| s.mod_id)
+                .filter(|id| !self.library().any(|m| m.mod_id == *id));
+            let id = stored_id.unwrap_or_else(|| self.next_local_id());
+            match self.store.add_file(&file_path, &file_name, id, id) {
+                Ok(hash) => {
+                    self.add_to_library(GameMod {
+                        name: file_name.clone(),
+                        zip_name: file_name.clone(),
+                        mod_id: id,
+                        file_id: id,
+                        hash,
+                        ..Default::default()
+                    });
+                }
+                Err(e) => {
+                    let context = format!(
+                        "Failed to add {} to {}",
+                        file_name,
+                        self.store.root().display()
+                    );
+                    self.notifier
+                        .error(Error::Context(context, Box::new(e.into())));
                 }
             }
         }
     }
+
+    /// Everything that goes into the state file, with the current installation first
+    fn current_state(&self) -> State {
+        let mut installations = self.installations.clone();
+        installations.insert(
+            0,
+            Installation {
+                name: self.installation_name.clone(),
+                game_path: self.game_path.clone(),
+                active: self.active.clone(),
+                inactive: self.inactive.clone(),
+            },
+        );
+        State {
+            version: STATE_VERSION,
+            settings: Settings {
+                api_key: self.api_key.clone(),
+                download_path: Some(self.download_path.clone()),
+                last_download: self.last_download.display().to_string(),
+                disable_strategy: self.disable_strategy,
+                launch_method: self.launch_method,
+                launch_args: self.launch_args.clone(),
+                dark_mode: self.dark_mode,
+                update_check: self.update_check,
+                last_update_check: self.last_update_check,
+                download_concurrency: self.download_concurrency,
+                log_level: self.log_level,
+            },
+            current: self.installation_name.clone(),
+            installations,
+        }
+    }
+
+    fn export_diagnostics(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("sdmm-diagnostics.zip")
+            .add_filter("Zip archive", &["zip"])
+            .save_file()
+        else {
+            return;
+        };
+        match diagnostics::export(&path, &self.current_state(), &self.versions) {
+            Ok(()) => self
+                .notifier
+                .info(format!("Saved diagnostics to {}", path.display())),
+            Err(e) => self
+                .notifier
+                .error(format!("Failed to export diagnostics: {e}")),
+        }
+    }
 }
 
 impl eframe::App for SDMMApp {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if !ctx.input().raw.dropped_files.is_empty() {
             self.handle_drag_drop(ctx);
         }
-        let base_path = PathBuf::from(crate::download::BASE_URI);
+        self.receive_metadata();
+        self.receive_packs();
+        self.notifications.poll();
+        if self
+            .update_check
+            .due(self.last_update_check, unix_time(), self.checked_updates)
+        {
+            self.check_updates();
+        }
+        if let Some(game) = &mut self.game
+            && game.poll()
+            && game.exited.is_some()
+        {
+            // SMAPI has finished writing its log, show what went wrong after a crash
+            let crashed = game.crashed();
+            self.load_smapi_log();
+            if crashed {
+                self.notifier.warning(format!(
+                    "The game exited with code {:?}",
+                    self.game.as_ref().unwrap().exited
+                ));
+                self.state = Menus::Logs;
+            }
+        }
         for (mod_name, downloaded, total, mod_id, file_id) in self.downloads_receiver.try_recv() {
             // if !self.downloads.contains_key(&mod_name) {
             if let Entry::Vacant(e) = self.downloads.entry(mod_name.clone()) {
@@ -761,111 +3696,115 @@ impl eframe::App for SDMMApp {
                 }
             }
         }
+        let mut added: Vec<GameMod> = vec![];
         for (mod_name, (downloaded, total, mod_id, file_id, saved)) in self.downloads.iter_mut() {
             if downloaded == total && !*saved {
-                let resp = self
-                    .web_client
-                    .get(format!(
-                        "https://{}/stardewvalley/mods/{mod_id}.json",
-                        base_path.display()
-                    ))
-                    .header("apikey", self.api_key.clone())
-                    .send();
-                let mod_details: ModDetails = match resp {
-                    Ok(res) => {
-                        if let Ok(details) = res.json::<ModDetails>() {
-                            details
-                        } else {
-                            ModDetails::default()
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error getting mod details: {e}");
-                        continue;
-                    }
-                };
-                let resp = self
-                    .web_client
-                    .get(format!(
-                        "https://{}/stardewvalley/mods/{mod_id}/files/{file_id}.json",
-                        base_path.display(),
-                    ))
-                    .header("apikey", self.api_key.clone())
-                    .send();
-                match resp {
-                    Ok(res) => match res.json::<crate::download::ModFileDetails>() {
-                        Ok(json) => {
-                            if self
-                                .inactive
-                                .iter()
-                                .filter(|m| {
-                                    m.mod_id == *mod_id as u64
-                                        && m.version
-                                            == json
-                                                .version
-                                                .clone()
-                                                .unwrap_or_else(|| String::from("0"))
-                                })
-                                .count()
-                                > 0
-                                || self
-                                    .active
-                                    .iter()
-                                    .filter(|m| {
-                                        m.mod_id == *mod_id as u64
-                                            && m.version
-                                                == json
-                                                    .version
-                                                    .clone()
-                                                    .unwrap_or_else(|| String::from("0"))
-                                    })
-                                    .count()
-                                    > 0
-                            {
-                                continue;
-                            }
-                            self.inactive.push(GameMod {
-                                name: mod_details.name,
-                                zip_name: mod_name.clone(),
-                                folder_name: "".into(),
-                                version: json.version.unwrap(),
-                                author: mod_details.author,
-                                link: format!(
-                                    "https://www.nexusmods.com/stardewvalley/mods/{mod_id}"
-                                ),
-                                mod_id: *mod_id as u64,
-                                file_id: *file_id as u64,
-                            });
-                        }
-                        Err(e) => {
-                            eprintln!("Response was not valid json: {e}");
-                        }
-                    },
-                    Err(e) => {
-                        eprintln!("Request failed: {e}");
-                    }
+                let partial = partial_path(self.store.root(), *mod_id, *file_id, mod_name);
+                if partial.exists()
+                    && let Err(e) =
+                        self.store
+                            .add_download(&partial, mod_name, *mod_id as u64, *file_id as u64)
+                {
+                    self.notifier.error(format!(
+                        "Failed to move {mod_name} into the archive store: {e}"
+                    ));
                 }
+                let hash = self
+                    .store
+                    .find_by_ids(*mod_id as u64, *file_id as u64)
+                    .unwrap_or_default();
                 *saved = true;
+                let known = !hash.is_empty()
+                    && self
+                        .inactive
+                        .iter()
+                        .chain(self.active.iter())
+                        .any(|m| m.hash == hash);
+                if known {
+                    continue;
+                }
+                self.metadata.details(*mod_id as u64, *file_id as u64);
+                added.push(GameMod {
+                    name: mod_name.clone(),
+                    zip_name: mod_name.clone(),
+                    link: format!("https://www.nexusmods.com/stardewvalley/mods/{mod_id}"),
+                    mod_id: *mod_id as u64,
+                    file_id: *file_id as u64,
+                    hash,
+                    pending: true,
+                    ..Default::default()
+                });
             }
         }
+        for r#mod in added {
+            self.add_to_library(r#mod);
+        }
         egui::TopBottomPanel::top("header").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 egui::widgets::global_dark_light_mode_switch(ui);
+                self.dark_mode = ui.visuals().dark_mode;
                 ui.selectable_value(&mut self.state, Menus::Settings, "");
                 ui.separator();
                 ui.selectable_value(&mut self.state, Menus::Browse, "Browse");
                 ui.selectable_value(&mut self.state, Menus::Downloading, "Downloading");
                 ui.selectable_value(&mut self.state, Menus::Mods, "Mods");
+                ui.selectable_value(&mut self.state, Menus::Console, "Console");
+                if ui
+                    .selectable_value(&mut self.state, Menus::Logs, "Logs")
+                    .clicked()
+                    && self.log_view.is_none()
+                {
+                    self.load_smapi_log();
+                }
+                let notifications = match self.notifications.unread {
+                    0 => String::from("Notifications"),
+                    unread => format!("Notifications ({unread})"),
+                };
+                ui.selectable_value(&mut self.state, Menus::Notifications, notifications);
+                ui.separator();
+                let running = self.game.as_ref().map_or(false, |g| g.running());
+                if !self.installations.is_empty() {
+                    let mut selected: Option<usize> = None;
+                    ui.add_enabled_ui(!running && self.bisect.is_none(), |ui| {
+                        egui::ComboBox::from_id_source("installation")
+                            .selected_text(&self.installation_name)
+                            .show_ui(ui, |ui| {
+                                let _ = ui.selectable_label(true, &self.installation_name);
+                                for (i, installation) in self.installations.iter().enumerate() {
+                                    if ui.selectable_label(false, &installation.name).clicked() {
+                                        selected = Some(i);
+                                    }
+                                }
+                            });
+                    });
+                    if let Some(index) = selected {
+                        self.switch_installation(index);
+                    }
+                }
+                if ui
+                    .add_enabled(!running, egui::Button::new("▶ Play"))
+                    .clicked()
+                {
+                    self.play(ctx);
+                }
+                ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                    let game = self.versions.game.as_deref().unwrap_or("not found");
+                    ui.label(format!("Stardew Valley {game}"));
+                    ui.separator();
+                    match &self.versions.smapi {
+                        Some(smapi) => ui.label(format!("SMAPI {smapi}")),
+                        None => ui.weak("SMAPI not installed"),
+                    };
+                });
             });
         });
         if self.needs_key {
             egui::Window::new("API KEY REQUIRED").show(ctx, |ui| {
-                ui.heading("A valid NexusMods API Key is currently needed to use this program, please provide one and restart.");
+                ui.heading("A valid NexusMods API Key is currently needed to use this program, please provide one.");
                 ui.hyperlink_to("Can be found at the bottom of this page", "https://www.nexusmods.com/users/myaccount?tab=api+access");
-                let _ = ui.add(egui::TextEdit::singleline(&mut self.api_key));
-                if ui.button("Submit").clicked() || ui.input().key_pressed(egui::Key::Enter) && !self.api_key.is_empty() {
-                    self.needs_key = false;
-                    frame.quit();
+                let _ = ui.add(egui::TextEdit::singleline(&mut self.settings_edit.api_key));
+                if ui.button("Submit").clicked() || ui.input().key_pressed(egui::Key::Enter) && !self.settings_edit.api_key.is_empty() {
+                    self.set_api_key(self.settings_edit.api_key.trim().to_string());
                 }
             });
         }
@@ -873,252 +3812,147 @@ impl eframe::App for SDMMApp {
             Menus::Browse => self.browse(ctx),
             Menus::Downloading => self.downloads_display(ctx),
             Menus::Mods => self.mods_display(ctx),
+            Menus::Console => self.console_display(ctx),
+            Menus::Logs => self.logs_display(ctx),
+            Menus::Notifications => {
+                egui::CentralPanel::default()
+                    .show(ctx, |ui| self.notifications.history_display(ui));
+            }
             Menus::Settings => self.settings_display(ctx),
         }
+        self.preview_display(ctx);
+        self.bisect_display(ctx);
+        self.install_choice_display(ctx);
+        self.import_display(ctx);
+        self.lock_report_display(ctx);
+        self.comparison_display(ctx);
+        self.notifications.toasts_display(ctx);
+        self.confirmation_display(ctx);
     }
 
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, "download_path", &self.download_path);
-        eframe::set_value(storage, "active_mods", &self.active);
-        eframe::set_value(storage, "inactive_mods", &self.inactive);
-        eframe::set_value(storage, "game_path", &self.game_path);
-        eframe::set_value(storage, "api_key", &self.api_key);
-        eframe::set_value(storage, "last_download", &self.last_download);
+    fn on_exit_event(&mut self) -> bool {
+        // The original mods are saved as active, not whichever half was being tested
+        if self.bisect.is_some() {
+            self.end_bisect(&[]);
+        }
+        true
     }
-}
 
-fn load_from_storage(
-    storage: &dyn Storage,
-    web_client: &Client,
-) -> (Vec<GameMod>, Vec<GameMod>, PathBuf, String, String) {
-    let base_path = PathBuf::from(crate::download::BASE_URI);
-
-    let mut active_mods: Vec<GameMod> = vec![];
-    let mut inactive_mods: Vec<GameMod> = vec![];
-    let mut game_path = PathBuf::new();
-    let mut last_download = String::new();
-    let mut api_key = String::new();
-    if let Some(path) = eframe::get_value(storage, "game_path") {
-        game_path = path;
-    } else {
-        let path = std::env::current_dir().unwrap();
-        let res = rfd::FileDialog::new()
-            .set_title("Stardew Valley Game Directory")
-            .add_filter("StardewValley", &["exe"])
-            .set_directory(&path)
-            .pick_folder();
-        if let Some(path) = res {
-            game_path = path;
+    fn save(&mut self, _storage: &mut dyn eframe::Storage) {
+        if !self.save_state {
+            return;
+        }
+        if let Err(e) = state::save(&self.current_state()) {
+            self.notifier
+                .error(format!("Failed to save the state: {e}"));
         }
     }
-    if let Some(last) = eframe::get_value(storage, "last_download") {
-        last_download = last;
-    }
-    if let Some(key) = eframe::get_value(storage, "api_key") {
-        api_key = key;
+}
+
+/// Archives added before the store existed are still found under their own name
+fn archive_path(store: &ArchiveStore, r#mod: &GameMod) -> PathBuf {
+    if r#mod.hash.is_empty() {
+        store.root().join(&r#mod.zip_name)
     } else {
-        // TODO: SOME KIND OF REQUEST FOR APIKEY
+        store.path(&r#mod.hash)
     }
+}
 
-    #[derive(Deserialize, Serialize)]
-    struct ModFileDetailsVec {
-        pub files: Vec<ModFileDetails>,
+fn mod_markers(ui: &mut egui::Ui, r#mod: &GameMod) {
+    if r#mod.pending {
+        ui.spinner();
+        ui.weak("(loading details)");
     }
-
-    // TODO: Make a request to check the status of the mods, check for different/newer version
-    if let Some(active) = eframe::get_value(storage, "active_mods") {
-        active_mods = active;
+    if r#mod.external {
+        ui.weak("(external)");
     }
-
-    if let Some(active_old) = eframe::get_value::<Vec<DepGameMod>>(storage, "active_mods") {
-        'main: for old in active_old {
-            let resp = web_client
-                .get(format!(
-                    "https://{}/stardewvalley/mods/{}/files.json",
-                    base_path.display(),
-                    old.id,
-                ))
-                .header("apikey", api_key.clone())
-                .send();
-
-            match resp {
-                Ok(res) => match res.json::<ModFileDetailsVec>() {
-                    Ok(details) => {
-                        for dets in details.files {
-                            if dets.file_name.unwrap() == old.zip_name
-                                && dets.version.unwrap() == old.version
-                            {
-                                active_mods.push(GameMod {
-                                    name: old.name.clone(),
-                                    zip_name: old.zip_name.clone(),
-                                    folder_name: old.folder_name.clone(),
-                                    version: old.version.clone(),
-                                    author: old.author.clone(),
-                                    link: old.link.clone(),
-                                    mod_id: old.id,
-                                    file_id: dets.file_id.unwrap_or(0),
-                                });
-                                continue 'main;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to serialize Vec<ModFileDetails>: {e}");
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to get files: {e}");
-                }
-            }
-            active_mods.push(GameMod {
-                name: old.name,
-                zip_name: old.zip_name,
-                folder_name: old.folder_name,
-                version: old.version,
-                author: old.author,
-                link: old.link,
-                mod_id: old.id,
-                file_id: 0,
-            });
-        }
+    if r#mod.missing {
+        ui.colored_label(egui::Color32::RED, "(missing)");
     }
-    if let Some(inactive) = eframe::get_value(storage, "inactive_mods") {
-        inactive_mods = inactive;
+    if let Some(required) = &r#mod.needs_api {
+        ui.colored_label(egui::Color32::GOLD, format!("(needs SMAPI {required})"))
+            .on_hover_text("The installed SMAPI is older than this mod requires");
     }
-    if let Some(inactive_old) = eframe::get_value::<Vec<DepGameMod>>(storage, "inactive_mods") {
-        'main: for old in inactive_old {
-            let resp = web_client
-                .get(format!(
-                    "https://{}/stardewvalley/mods/{}/files.json",
-                    base_path.display(),
-                    old.id,
-                ))
-                .header("apikey", api_key.clone())
-                .send();
-
-            match resp {
-                Ok(res) => match res.json::<ModFileDetailsVec>() {
-                    Ok(details) => {
-                        for dets in details.files {
-                            if dets.file_name.unwrap() == old.zip_name
-                                && dets.version.unwrap() == old.version
-                            {
-                                inactive_mods.push(GameMod {
-                                    name: old.name.clone(),
-                                    zip_name: old.zip_name.clone(),
-                                    folder_name: old.folder_name.clone(),
-                                    version: old.version.clone(),
-                                    author: old.author.clone(),
-                                    link: old.link.clone(),
-                                    mod_id: old.id,
-                                    file_id: dets.file_id.unwrap_or(0),
-                                });
-                                continue 'main;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to serialize Vec<ModFileDetails>: {e}")
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to get files: {e}")
-                }
-            }
-            inactive_mods.push(GameMod {
-                name: old.name,
-                zip_name: old.zip_name,
-                folder_name: old.folder_name,
-                version: old.version,
-                author: old.author,
-                link: old.link,
-                mod_id: old.id,
-                file_id: 0,
-            });
-        }
+    if let Some(latest) = &r#mod.latest_version
+        && compare_versions(latest, &r#mod.version) == std::cmp::Ordering::Greater
+    {
+        ui.colored_label(egui::Color32::LIGHT_BLUE, format!("(update {latest})"))
+            .on_hover_text("A newer version is available on NexusMods");
     }
+}
 
-    (
-        active_mods,
-        inactive_mods,
-        game_path,
-        last_download,
-        api_key,
-    )
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
 }
 
-fn setup_download_path(context: &CreationContext<'_>) -> PathBuf {
-    if let Some(storage) = context.storage {
-        if let Some(dir) = eframe::get_value(storage, "download_path") {
-            return dir;
-        } else if let Some(proj_dirs) = ProjectDirs::from("", "", crate::PROJECT_NAME) {
-            let dir = proj_dirs.data_dir();
-            if let Ok(d) = read_dir(&dir) {
-                let directories = d.filter(|d| d.as_ref().unwrap().file_name() == "mods");
-                if directories.count() == 0 {
-                    create_dir(dir.join("mods")).unwrap();
-                }
-            }
-            return dir.join("mods");
+/// Collects every folder below `mods_path` that SMAPI would load, the same way SMAPI searches
+/// folders without a manifest for nested mods
+fn find_manifests(mods_path: &Path, relative: &Path, found: &mut Vec<(String, Manifest)>) {
+    let Ok(entries) = read_dir(mods_path.join(relative)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // SMAPI skips dot folders, which is also where disabled mods live
+        if name.starts_with('.') || !entry.path().is_dir() {
+            continue;
         }
-    } else if let Some(proj_dirs) = ProjectDirs::from("", "", crate::PROJECT_NAME) {
-        let dir = proj_dirs.data_dir();
-        if let Ok(d) = read_dir(&dir) {
-            let directories = d.filter(|d| d.as_ref().unwrap().file_name() == "mods");
-            if directories.count() == 0 {
-                create_dir(dir.join("mods")).unwrap();
-            }
+        let folder = relative.join(&name);
+        match read_manifest(&entry.path()) {
+            Some(manifest) => found.push((folder.display().to_string(), manifest)),
+            None => find_manifests(mods_path, &folder, found),
         }
-        return dir.join("mods");
     }
-    panic!("Could not get or create the download path");
 }
 
-fn unzip(file: &File, output_location: &Path) -> String {
-    let folder_name: String;
-    let mut archive = zip::ZipArchive::new(file).unwrap();
-    // Get the folder name for the mod
-    {
-        let first = archive.by_index(0).unwrap();
-        if first.is_dir() {
-            folder_name = first.enclosed_name().unwrap().display().to_string();
-        } else {
-            folder_name = first
-                .enclosed_name()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .display()
-                .to_string();
-        }
-    }
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let outpath = match file.enclosed_name() {
-            Some(path) => path.to_owned(),
-            None => continue,
+/// Finds the game folder on its own where possible, otherwise asks for it
+fn locate_game_path() -> PathBuf {
+    if let Some(path) = game::detect_game_path() {
+        info!("Found the game in {}", path.display());
+        return path;
+    }
+    loop {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Stardew Valley Game Directory")
+            .set_directory(std::env::current_dir().unwrap_or_default())
+            .pick_folder()
+        else {
+            return PathBuf::new();
         };
-        let outpath = output_location.join(outpath);
-
-        if (*file.name()).ends_with('/') {
-            create_dir_all(&outpath).unwrap();
-        } else {
-            if let Some(p) = outpath.parent() && !p.exists() {
-                create_dir_all(&p).unwrap();
-            }
-            let mut outfile = File::create(&outpath).unwrap();
-            io::copy(&mut file, &mut outfile).unwrap();
+        if game::is_game_path(&path) {
+            return path;
         }
+        rfd::MessageDialog::new()
+            .set_title("Not a game folder")
+            .set_description(&format!("{} was not found in {}", GAME_DLL, path.display()))
+            .show();
     }
-    folder_name
 }
 
-fn list_files(file: &File) -> Vec<(String, bool)> {
-    let mut files: Vec<(String, bool)> = vec![];
-    let mut archive = zip::ZipArchive::new(file).unwrap();
-    for i in 0..archive.len() {
-        let file = archive.by_index(i).unwrap();
-        files.push((file.name().to_string(), file.is_dir()));
+fn setup_download_path(saved: Option<PathBuf>) -> Result<PathBuf, Error> {
+    if let Some(dir) = saved {
+        return Ok(dir);
     }
-    files
+    let dir = default_download_path().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "no data directory for this user")
+    })?;
+    create_dir_all(&dir).context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+fn default_download_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", crate::PROJECT_NAME).map(|dirs| dirs.data_dir().join("mods"))
+}
+
+/// Asks where to keep the archives when the data directory can't be used, falling back to it
+/// anyway, or to the temp directory without one, so the path never depends on where SDMM was
+/// started from
+fn pick_download_path() -> PathBuf {
+    rfd::FileDialog::new()
+        .set_title("Folder for downloaded mods")
+        .pick_folder()
+        .or_else(default_download_path)
+        .unwrap_or_else(|| std::env::temp_dir().join(crate::PROJECT_NAME))
 }
@@ -0,0 +1,493 @@
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// A folder inside a mod archive that gets installed as its own folder in the Mods directory
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModUnit {
+    /// Location of the unit inside the archive, empty when it sits at the archive root
+    pub root: PathBuf,
+    /// Name of the folder created for the unit in the Mods directory
+    pub folder_name: String,
+}
+
+pub fn list_files(file: &File) -> Result<Vec<(String, bool)>> {
+    let mut files: Vec<(String, bool)> = vec![];
+    let mut archive = ZipArchive::new(file)?;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        files.push((file.name().to_string(), file.is_dir()));
+    }
+    Ok(files)
+}
+
+/// Finds every folder in the archive that contains a `manifest.json`.
+///
+/// Wrapper directories are dropped so only the folder holding the manifest is installed, and
+/// manifests nested inside another unit are treated as part of that unit, the same way SMAPI
+/// loads them. Archives without any manifest fall back to their top level folders, or to a
+/// single folder named `fallback_name` when they have loose files at the root.
+pub fn find_mod_units(file: &File, fallback_name: &str) -> Result<Vec<ModUnit>> {
+    let entries = entry_paths(file)?;
+    let mut roots: Vec<PathBuf> = entries
+        .iter()
+        .filter(|(path, is_dir)| {
+            !is_dir
+                && path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().eq_ignore_ascii_case("manifest.json"))
+                    .unwrap_or(false)
+        })
+        .map(|(path, _)| path.parent().map(Path::to_path_buf).unwrap_or_default())
+        .collect();
+    if roots.is_empty() {
+        if entries
+            .iter()
+            .any(|(path, is_dir)| !is_dir && path.components().count() == 1)
+        {
+            roots.push(PathBuf::new());
+        } else {
+            for (path, _) in &entries {
+                if let Some(top) = path.components().next() {
+                    let top = PathBuf::from(top.as_os_str());
+                    if !roots.contains(&top) {
+                        roots.push(top);
+                    }
+                }
+            }
+        }
+    }
+    roots.sort_by_key(|root| root.components().count());
+
+    let mut units: Vec<ModUnit> = vec![];
+    for root in roots {
+        if units.iter().any(|unit| root.starts_with(&unit.root)) {
+            continue;
+        }
+        let folder_name = match root.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => fallback_name.to_string(),
+        };
+        units.push(ModUnit { root, folder_name });
+    }
+    Ok(units)
+}
+
+/// The parts of a SMAPI `manifest.json` SDMM cares about
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Manifest {
+    pub name: String,
+    pub author: String,
+    #[serde(deserialize_with = "version_string")]
+    pub version: String,
+    pub description: String,
+    #[serde(rename = "UniqueID")]
+    pub unique_id: String,
+    pub entry_dll: Option<String>,
+    pub content_pack_for: Option<ContentPackFor>,
+    pub minimum_api_version: Option<String>,
+    pub dependencies: Vec<Dependency>,
+    pub update_keys: Vec<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct ContentPackFor {
+    #[serde(rename = "UniqueID")]
+    pub unique_id: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Dependency {
+    #[serde(rename = "UniqueID")]
+    pub unique_id: String,
+    pub minimum_version: Option<String>,
+    #[serde(default = "required")]
+    pub is_required: bool,
+}
+
+fn required() -> bool {
+    true
+}
+
+// Old manifests spell the version out as an object of its parts
+fn version_string<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(match value {
+        serde_json::Value::String(version) => version,
+        serde_json::Value::Object(parts) => {
+            let part = |key: &str| parts.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+            format!(
+                "{}.{}.{}",
+                part("MajorVersion"),
+                part("MinorVersion"),
+                part("PatchVersion")
+            )
+        }
+        _ => String::new(),
+    })
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Option<Manifest> {
+        serde_json::from_str(&strip_json_extensions(text)).ok()
+    }
+}
+
+/// Whether two UniqueIDs name the same mod, SMAPI ignores case and an empty id names nothing
+pub fn same_unique_id(a: &str, b: &str) -> bool {
+    !a.is_empty() && a.eq_ignore_ascii_case(b)
+}
+
+/// Reads the manifest of an installed mod folder
+pub fn read_manifest(folder: &Path) -> Option<Manifest> {
+    Manifest::parse(&read_to_string(folder.join("manifest.json")).ok()?)
+}
+
+/// Everything shown about an archive before it is installed
+pub struct ArchivePreview {
+    pub files: Vec<(String, bool)>,
+    /// Uncompressed size of all files in bytes
+    pub total_size: u64,
+    pub units: Vec<(ModUnit, Option<Manifest>)>,
+}
+
+impl ArchivePreview {
+    /// Whether the archive ships SMAPI mods with code rather than only content packs
+    pub fn has_code(&self) -> bool {
+        self.units
+            .iter()
+            .any(|(_, manifest)| manifest.as_ref().map_or(false, |m| m.entry_dll.is_some()))
+            || self
+                .files
+                .iter()
+                .any(|(name, _)| name.to_lowercase().ends_with(".dll"))
+    }
+}
+
+pub fn preview(file: &File, fallback_name: &str) -> Result<ArchivePreview> {
+    let mut archive = ZipArchive::new(file)?;
+    let mut total_size = 0;
+    for i in 0..archive.len() {
+        total_size += archive.by_index(i)?.size();
+    }
+    let files = list_files(file)?;
+    let mut units = vec![];
+    for unit in find_mod_units(file, fallback_name)? {
+        let manifest = read_manifest_entry(&mut archive, &unit.root);
+        units.push((unit, manifest));
+    }
+    Ok(ArchivePreview {
+        files,
+        total_size,
+        units,
+    })
+}
+
+fn read_manifest_entry(archive: &mut ZipArchive<&File>, root: &Path) -> Option<Manifest> {
+    let name = archive
+        .file_names()
+        .find(|name| {
+            let path = Path::new(name);
+            path.parent() == Some(root)
+                && path.file_name().map_or(false, |n| {
+                    n.to_string_lossy().eq_ignore_ascii_case("manifest.json")
+                })
+        })?
+        .to_string();
+    let mut text = String::new();
+    archive
+        .by_name(&name)
+        .ok()?
+        .read_to_string(&mut text)
+        .ok()?;
+    Manifest::parse(&text)
+}
+
+/// Mod authors write manifests by hand, so allow the comments, trailing commas and byte
+/// order mark SMAPI itself accepts
+fn strip_json_extensions(text: &str) -> String {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push(c);
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '}' | ']' => {
+                let trimmed = out.trim_end().len();
+                if out[..trimmed].ends_with(',') {
+                    out.truncate(trimmed - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// What an install wrote, relative to the Mods directory
+#[derive(Default, Debug)]
+pub struct Installed {
+    pub folders: Vec<String>,
+    pub files: Vec<PathBuf>,
+}
+
+/// Lists the files the given units would write, relative to the Mods directory
+pub fn planned_files(file: &File, units: &[ModUnit]) -> Result<Vec<PathBuf>> {
+    Ok(entry_paths(file)?
+        .into_iter()
+        .filter(|(_, is_dir)| !is_dir)
+        .filter_map(|(path, _)| destination(&path, units))
+        .collect())
+}
+
+/// Extracts the given units into `mods_path`, refusing units that would share a folder as
+/// they would overwrite each other
+pub fn install_units(file: &File, units: &[ModUnit], mods_path: &Path) -> Result<Installed> {
+    for (i, unit) in units.iter().enumerate() {
+        if units[..i]
+            .iter()
+            .any(|other| other.folder_name.eq_ignore_ascii_case(&unit.folder_name))
+        {
+            return Err(Error::SameFolder(unit.folder_name.clone()));
+        }
+    }
+    let mut installed = Installed {
+        folders: units.iter().map(|unit| unit.folder_name.clone()).collect(),
+        files: vec![],
+    };
+    let mut archive = ZipArchive::new(file)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(relative) = entry
+            .enclosed_name()
+            .and_then(|path| destination(path, units))
+        else {
+            continue;
+        };
+        let outpath = mods_path.join(&relative);
+        if entry.is_dir() {
+            create_dir_all(&outpath)?;
+        } else {
+            if let Some(p) = outpath.parent()
+                && !p.exists()
+            {
+                create_dir_all(p)?;
+            }
+            let mut outfile = File::create(&outpath)?;
+            io::copy(&mut entry, &mut outfile)?;
+            installed.files.push(relative);
+        }
+    }
+    Ok(installed)
+}
+
+/// Maps a path inside the archive to where it is installed, if it belongs to one of the units
+fn destination(path: &Path, units: &[ModUnit]) -> Option<PathBuf> {
+    if path.starts_with("__MACOSX") {
+        return None;
+    }
+    // The deepest unit wins so a nested root is never extracted under its parent
+    let unit = units
+        .iter()
+        .filter(|unit| path.starts_with(&unit.root))
+        .max_by_key(|unit| unit.root.components().count())?;
+    let relative = path.strip_prefix(&unit.root).ok()?;
+    Some(Path::new(&unit.folder_name).join(relative))
+}
+
+fn entry_paths(file: &File) -> Result<Vec<(PathBuf, bool)>> {
+    let mut archive = ZipArchive::new(file)?;
+    let mut paths = vec![];
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        // Resource forks added by macOS archivers are never part of a mod
+        if path.starts_with("__MACOSX") {
+            continue;
+        }
+        paths.push((path.to_path_buf(), entry.is_dir()));
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn archive(files: &[&str]) -> File {
+        let mut zip = ZipWriter::new(tempfile::tempfile().unwrap());
+        for file in files {
+            zip.start_file(*file, FileOptions::default()).unwrap();
+            zip.write_all(b"{}").unwrap();
+        }
+        zip.finish().unwrap()
+    }
+
+    fn units(files: &[&str]) -> Vec<(String, String)> {
+        let file = archive(files);
+        find_mod_units(&file, "Fallback")
+            .unwrap()
+            .into_iter()
+            .map(|unit| (unit.root.display().to_string(), unit.folder_name))
+            .collect()
+    }
+
+    fn unit(root: &str, folder_name: &str) -> (String, String) {
+        (root.to_string(), folder_name.to_string())
+    }
+
+    #[test]
+    fn manifest_at_root_uses_fallback_name() {
+        assert_eq!(
+            units(&["manifest.json", "Mod.dll"]),
+            vec![unit("", "Fallback")]
+        );
+    }
+
+    #[test]
+    fn wrapper_folders_are_dropped() {
+        assert_eq!(
+            units(&[
+                "Wrapper/Inner/Mod/manifest.json",
+                "Wrapper/Inner/Mod/Mod.dll",
+                "Wrapper/readme.txt"
+            ]),
+            vec![unit("Wrapper/Inner/Mod", "Mod")]
+        );
+    }
+
+    #[test]
+    fn every_manifest_folder_is_a_unit() {
+        assert_eq!(
+            units(&[
+                "Pack/A/manifest.json",
+                "Pack/B/manifest.json",
+                "C/manifest.json"
+            ]),
+            vec![unit("C", "C"), unit("Pack/A", "A"), unit("Pack/B", "B")]
+        );
+    }
+
+    #[test]
+    fn nested_manifests_belong_to_their_parent() {
+        assert_eq!(
+            units(&["Mod/manifest.json", "Mod/assets/Extra/manifest.json"]),
+            vec![unit("Mod", "Mod")]
+        );
+    }
+
+    #[test]
+    fn manifest_names_ignore_case() {
+        assert_eq!(units(&["Mod/Manifest.JSON"]), vec![unit("Mod", "Mod")]);
+    }
+
+    #[test]
+    fn without_manifest_loose_files_use_fallback_name() {
+        assert_eq!(
+            units(&["content.json", "assets/a.png"]),
+            vec![unit("", "Fallback")]
+        );
+    }
+
+    #[test]
+    fn without_manifest_top_level_folders_are_units() {
+        assert_eq!(
+            units(&[
+                "A/content.json",
+                "B/assets/b.png",
+                "__MACOSX/A/._content.json"
+            ]),
+            vec![unit("A", "A"), unit("B", "B")]
+        );
+    }
+
+    #[test]
+    fn files_install_below_their_unit_folder() {
+        let file = archive(&[
+            "Wrapper/Mod/manifest.json",
+            "Wrapper/Mod/assets/a.png",
+            "Wrapper/readme.txt",
+        ]);
+        let units = find_mod_units(&file, "Fallback").unwrap();
+        assert_eq!(
+            planned_files(&file, &units).unwrap(),
+            vec![
+                PathBuf::from("Mod/manifest.json"),
+                PathBuf::from("Mod/assets/a.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn units_sharing_a_folder_are_refused() {
+        let file = archive(&["Option A/Mod/manifest.json", "Option B/Mod/manifest.json"]);
+        let units = find_mod_units(&file, "Fallback").unwrap();
+        assert_eq!(units.len(), 2);
+        let mods = tempfile::tempdir().unwrap();
+        let mods_path = mods.path();
+        assert!(matches!(
+            install_units(&file, &units, mods_path),
+            Err(Error::SameFolder(folder)) if folder == "Mod"
+        ));
+        let installed = install_units(&file, &units[1..], mods_path).unwrap();
+        assert_eq!(installed.folders, vec!["Mod"]);
+        assert_eq!(installed.files, vec![PathBuf::from("Mod/manifest.json")]);
+    }
+
+    #[test]
+    fn manifests_may_have_comments_and_trailing_commas() {
+        let manifest = Manifest::parse(
+            "\u{feff}{\n  // comment\n  \"Name\": \"Mod\", /* block */\n  \"UniqueID\": \"a.b\",\n}",
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "Mod");
+        assert_eq!(manifest.unique_id, "a.b");
+    }
+}
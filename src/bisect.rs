@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+/// Identifies a mod by its mod and file id
+pub type ModKey = (u64, u64);
+
+/// What the current test is meant to show
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Check {
+    /// Whether the cause is in the tested half of the suspects
+    Half,
+    /// Whether the problem goes away with every mod disabled, asked when no answer showed it
+    WithoutMods,
+    /// Whether the remaining suspects alone cause the problem, asked when no answer showed it
+    Culprit,
+}
+
+/// Narrows down which active mod causes a problem by repeatedly testing half of the suspects
+pub struct Bisect {
+    /// Active mods when the bisect started, restored when it ends
+    pub original: Vec<ModKey>,
+    /// Mods that may still be causing the problem
+    pub suspects: Vec<ModKey>,
+    /// The half of the suspects enabled for the current test
+    pub testing: Vec<ModKey>,
+    /// Mods each mod requires to load
+    dependencies: HashMap<ModKey, Vec<ModKey>>,
+    pub step: usize,
+    pub check: Check,
+    /// No further narrowing is possible, the remaining suspects are the answer
+    pub finished: bool,
+    /// The problem happened with every mod disabled, so no mod is the cause
+    pub without_mods: bool,
+    /// Whether any answer so far was broken, and whether any was working
+    seen_broken: bool,
+    seen_working: bool,
+}
+
+impl Bisect {
+    pub fn start(active: Vec<ModKey>, dependencies: HashMap<ModKey, Vec<ModKey>>) -> Bisect {
+        let mut bisect = Bisect {
+            original: active.clone(),
+            suspects: active,
+            testing: vec![],
+            dependencies,
+            step: 0,
+            check: Check::Half,
+            finished: false,
+            without_mods: false,
+            seen_broken: false,
+            seen_working: false,
+        };
+        bisect.split();
+        bisect
+    }
+
+    /// Mods to enable for the current test, the tested half plus everything it depends on
+    pub fn wanted(&self) -> Vec<ModKey> {
+        let mut wanted: Vec<ModKey> = vec![];
+        let mut queue = self.testing.clone();
+        while let Some(key) = queue.pop() {
+            if wanted.contains(&key) {
+                continue;
+            }
+            wanted.push(key);
+            if let Some(dependencies) = self.dependencies.get(&key) {
+                queue.extend(dependencies.iter().filter(|d| self.original.contains(d)));
+            }
+        }
+        wanted
+    }
+
+    /// Records whether the problem still happened with the current test's mods enabled
+    pub fn answer(&mut self, still_broken: bool) {
+        self.step += 1;
+        if still_broken {
+            self.seen_broken = true;
+        } else {
+            self.seen_working = true;
+        }
+        match self.check {
+            Check::Half => {
+                let wanted = self.wanted();
+                let before = self.suspects.len();
+                if still_broken {
+                    self.suspects.retain(|key| wanted.contains(key));
+                } else {
+                    self.suspects.retain(|key| !wanted.contains(key));
+                }
+                // Suspects that all depend on each other can not be split any further
+                if self.suspects.len() == before {
+                    self.finished = true;
+                }
+            }
+            Check::WithoutMods => {
+                self.finished = true;
+                if still_broken {
+                    self.without_mods = true;
+                    self.suspects.clear();
+                }
+            }
+            Check::Culprit => {
+                self.finished = true;
+                if !still_broken {
+                    self.suspects.clear();
+                }
+            }
+        }
+        self.split();
+    }
+
+    fn split(&mut self) {
+        if self.suspects.len() <= 1 {
+            self.finished = true;
+        }
+        self.testing = vec![];
+        if self.suspects.is_empty() {
+            self.finished = true;
+        } else if !self.finished {
+            self.testing = self.suspects[..(self.suspects.len() + 1) / 2].to_vec();
+        } else if !self.seen_working {
+            // Every answer was broken, which no mod is to blame for if it stays broken without any
+            self.finished = false;
+            self.check = Check::WithoutMods;
+        } else if !self.seen_broken {
+            // Every answer was working, so make sure the suspects do break the game on their own
+            self.finished = false;
+            self.check = Check::Culprit;
+            self.testing = self.suspects.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mods(count: u64) -> Vec<ModKey> {
+        (1..=count).map(|id| (id, id)).collect()
+    }
+
+    /// Answers each test with whether `broken` says the enabled mods break the game
+    fn run(bisect: &mut Bisect, broken: impl Fn(&[ModKey]) -> bool) {
+        for _ in 0..32 {
+            if bisect.finished {
+                return;
+            }
+            let wanted = bisect.wanted();
+            bisect.answer(broken(&wanted));
+        }
+        panic!("the bisect never finished");
+    }
+
+    #[test]
+    fn finds_the_single_culprit() {
+        for culprit in mods(9) {
+            let mut bisect = Bisect::start(mods(9), HashMap::new());
+            run(&mut bisect, |wanted| wanted.contains(&culprit));
+            assert_eq!(bisect.suspects, vec![culprit]);
+            assert!(!bisect.without_mods);
+            assert!(bisect.step <= 6);
+        }
+    }
+
+    #[test]
+    fn enables_dependencies_of_tested_mods() {
+        let dependencies = HashMap::from([((1, 1), vec![(4, 4)])]);
+        let bisect = Bisect::start(mods(4), dependencies);
+        assert_eq!(bisect.testing, vec![(1, 1), (2, 2)]);
+        let mut wanted = bisect.wanted();
+        wanted.sort();
+        assert_eq!(wanted, vec![(1, 1), (2, 2), (4, 4)]);
+    }
+
+    #[test]
+    fn working_with_every_mod_finds_nothing() {
+        let mut bisect = Bisect::start(mods(4), HashMap::new());
+        run(&mut bisect, |_| false);
+        assert_eq!(bisect.check, Check::Culprit);
+        assert!(bisect.suspects.is_empty());
+        assert!(!bisect.without_mods);
+    }
+
+    #[test]
+    fn broken_without_mods_blames_none() {
+        let mut bisect = Bisect::start(mods(4), HashMap::new());
+        run(&mut bisect, |_| true);
+        assert_eq!(bisect.check, Check::WithoutMods);
+        assert!(bisect.suspects.is_empty());
+        assert!(bisect.without_mods);
+    }
+
+    #[test]
+    fn culprit_is_confirmed_without_mods() {
+        let mut bisect = Bisect::start(mods(4), HashMap::new());
+        run(&mut bisect, |wanted| wanted.contains(&(1, 1)));
+        assert_eq!(bisect.check, Check::WithoutMods);
+        assert_eq!(bisect.suspects, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn mods_depending_on_each_other_are_blamed_together() {
+        let dependencies = HashMap::from([((1, 1), vec![(2, 2)]), ((2, 2), vec![(1, 1)])]);
+        let mut bisect = Bisect::start(mods(2), dependencies);
+        run(&mut bisect, |wanted| wanted.contains(&(2, 2)));
+        assert_eq!(bisect.suspects, mods(2));
+        assert_eq!(bisect.step, 2);
+    }
+
+    #[test]
+    fn single_mod_is_checked_both_ways() {
+        let mut bisect = Bisect::start(mods(1), HashMap::new());
+        assert_eq!(bisect.check, Check::WithoutMods);
+        bisect.answer(false);
+        assert_eq!(bisect.check, Check::Culprit);
+        assert_eq!(bisect.wanted(), mods(1));
+        bisect.answer(true);
+        assert!(bisect.finished);
+        assert_eq!(bisect.suspects, mods(1));
+    }
+
+    #[test]
+    fn no_mods_finishes_at_once() {
+        let bisect = Bisect::start(vec![], HashMap::new());
+        assert!(bisect.finished);
+        assert!(bisect.suspects.is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::error::{Context, Error, Result};
+use crate::game::Versions;
+use crate::logging;
+use crate::smapi_log::latest_log_path;
+use crate::state::State;
+use std::fmt::Write as _;
+use std::fs::{read, read_dir, File};
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+const REDACTED: &str = "<redacted>";
+
+/// Writes everything needed to look into someone else's problem into one zip: the state
+/// without secrets, the mod lists, versions, SDMM's logs and the latest SMAPI log
+pub fn export(path: &Path, state: &State, versions: &Versions) -> Result<()> {
+    let file = File::create(path)?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default();
+
+    let mut json = serde_json::to_value(state).map_err(Error::State)?;
+    if let Some(settings) = json.get_mut("settings").and_then(|s| s.as_object_mut()) {
+        // nxm links carry a download key of their own
+        for secret in ["api_key", "last_download"] {
+            if let Some(value) = settings.get_mut(secret)
+                && value.as_str().map_or(false, |s| !s.is_empty())
+            {
+                *value = REDACTED.into();
+            }
+        }
+    }
+    let json = serde_json::to_string_pretty(&json).map_err(Error::State)?;
+    zip.start_file("state.json", options)?;
+    zip.write_all(json.as_bytes())?;
+
+    zip.start_file("versions.txt", options)?;
+    zip.write_all(version_summary(versions).as_bytes())?;
+
+    zip.start_file("mods.txt", options)?;
+    zip.write_all(mod_list(state).as_bytes())?;
+
+    if let Some(dir) = logging::log_dir()
+        && let Ok(entries) = read_dir(dir)
+    {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".log") {
+                add_file(&mut zip, &format!("logs/{name}"), &entry.path())?;
+            }
+        }
+    }
+    if let Some(smapi_log) = latest_log_path()
+        && smapi_log.exists()
+    {
+        add_file(&mut zip, "SMAPI-latest.txt", &smapi_log)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn add_file(zip: &mut ZipWriter<File>, name: &str, path: &Path) -> Result<()> {
+    let bytes = read(path).context(|| format!("Failed to read {}", path.display()))?;
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(redact_links(&String::from_utf8_lossy(&bytes)).as_bytes())?;
+    Ok(())
+}
+
+/// Blanks the download key and expiry of any nxm or download link that made it into a log
+fn redact_links(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((start, param)) = ["key=", "expires="]
+        .iter()
+        .filter_map(|param| rest.find(param).map(|start| (start, param)))
+        .min()
+    {
+        let value = start + param.len();
+        let end = rest[value..]
+            .find(|c: char| c == '&' || c == '"' || c == '\'' || c.is_whitespace())
+            .map_or(rest.len(), |end| value + end);
+        redacted.push_str(&rest[..value]);
+        if end > value {
+            redacted.push_str(REDACTED);
+        }
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+fn version_summary(versions: &Versions) -> String {
+    format!(
+        "SDMM {}\nStardew Valley {}\nSMAPI {}\nOS {} {}\n",
+        env!("CARGO_PKG_VERSION"),
+        versions.game.as_deref().unwrap_or("not found"),
+        versions.smapi.as_deref().unwrap_or("not installed"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    )
+}
+
+/// One line per mod of every installation, easier to read than the state
+fn mod_list(state: &State) -> String {
+    let mut list = String::new();
+    for installation in &state.installations {
+        let _ = writeln!(
+            list,
+            "# {} ({})",
+            installation.name,
+            installation.game_path.display()
+        );
+        for (status, mods) in [
+            ("active", &installation.active),
+            ("inactive", &installation.inactive),
+        ] {
+            for r#mod in mods {
+                let _ = writeln!(
+                    list,
+                    "{status}\t{}\t{}\tmod {}\tfile {}\t{}",
+                    r#mod.name,
+                    r#mod.version,
+                    r#mod.mod_id,
+                    r#mod.file_id,
+                    r#mod.unique_ids.join(", ")
+                );
+            }
+        }
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_lose_their_key() {
+        let log = "[INFO] Received nxm://stardewvalley/mods/1915/files/76360?key=s3cr3t&expires=1700000000&user_id=42\n\
+                   [ERROR] Download of https://cf-files.nexusmods.com/a.zip?md5=abc&expires=1700000000 failed\n";
+        let redacted = redact_links(log);
+        assert!(!redacted.contains("s3cr3t"));
+        assert!(!redacted.contains("1700000000"));
+        assert!(redacted.contains("76360?key=<redacted>&expires=<redacted>&user_id=42\n"));
+        assert!(redacted.contains("md5=abc&expires=<redacted> failed"));
+    }
+
+    #[test]
+    fn other_lines_stay_as_they_are() {
+        let log = "[INFO] Installed Content Patcher 1.28.0\n[WARN] key= nothing\n";
+        assert_eq!(redact_links(log), log);
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{create_dir_all, remove_dir_all, rename};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Folder inside the Mods directory that holds disabled mods, SMAPI skips folders starting
+/// with a dot
+pub const DISABLED_FOLDER: &str = ".disabled";
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+pub enum DisableStrategy {
+    /// Delete the mod's files and extract the archive again when it is enabled
+    #[default]
+    Delete,
+    /// Move the mod's folders into the disabled folder and back
+    Rename,
+}
+
+pub fn disabled_path(mods_path: &Path, folder: &str) -> PathBuf {
+    mods_path.join(DISABLED_FOLDER).join(folder)
+}
+
+/// Stashed copies that would have to make room for stashing the folders
+pub fn stashed(mods_path: &Path, folders: &[String]) -> Vec<PathBuf> {
+    folders
+        .iter()
+        .filter(|folder| mods_path.join(folder).exists())
+        .map(|folder| disabled_path(mods_path, folder))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// Moves the folders out of SMAPI's sight, refusing to replace anything disabled under the same
+/// name
+pub fn stash(mods_path: &Path, folders: &[String]) -> io::Result<()> {
+    if let Some(taken) = stashed(mods_path, folders).first() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already holds a disabled mod", taken.display()),
+        ));
+    }
+    for folder in folders {
+        let from = mods_path.join(folder);
+        if !from.exists() {
+            continue;
+        }
+        let to = disabled_path(mods_path, folder);
+        if let Some(parent) = to.parent() {
+            create_dir_all(parent)?;
+        }
+        rename(from, to)?;
+    }
+    Ok(())
+}
+
+/// Moves stashed folders back into the Mods directory, checking every folder is stashed and
+/// free first so a mod is never restored halfway
+pub fn restore(mods_path: &Path, folders: &[String]) -> io::Result<()> {
+    for folder in folders {
+        let from = disabled_path(mods_path, folder);
+        if !from.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is missing", from.display()),
+            ));
+        }
+        let to = mods_path.join(folder);
+        if to.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already installed", to.display()),
+            ));
+        }
+    }
+    for folder in folders {
+        let from = disabled_path(mods_path, folder);
+        let to = mods_path.join(folder);
+        if let Some(parent) = to.parent() {
+            create_dir_all(parent)?;
+        }
+        rename(from, to)?;
+    }
+    Ok(())
+}
+
+/// Deletes the stashed copies of the folders
+pub fn discard(mods_path: &Path, folders: &[String]) -> io::Result<()> {
+    for folder in folders {
+        if let Err(e) = remove_dir_all(disabled_path(mods_path, folder))
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    fn folders(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    /// A Mods folder holding a folder with a manifest for each name
+    fn mods(names: &[&str]) -> tempfile::TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        for name in names {
+            create_dir_all(temp.path().join(name)).unwrap();
+            write(temp.path().join(name).join("manifest.json"), *name).unwrap();
+        }
+        temp
+    }
+
+    #[test]
+    fn stash_and_restore_move_folders() {
+        let temp = mods(&["A", "B"]);
+        let mods_path = temp.path();
+        stash(mods_path, &folders(&["A", "B"])).unwrap();
+        assert!(!mods_path.join("A").exists());
+        assert!(disabled_path(mods_path, "B").join("manifest.json").exists());
+
+        restore(mods_path, &folders(&["A", "B"])).unwrap();
+        assert!(mods_path.join("A/manifest.json").exists());
+        assert!(mods_path.join("B/manifest.json").exists());
+        assert!(!disabled_path(mods_path, "A").exists());
+    }
+
+    #[test]
+    fn stash_refuses_to_replace_disabled_copy() {
+        let temp = mods(&["A", "B"]);
+        let mods_path = temp.path();
+        stash(mods_path, &folders(&["A"])).unwrap();
+        create_dir_all(mods_path.join("A")).unwrap();
+
+        let e = stash(mods_path, &folders(&["B", "A"])).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert!(mods_path.join("B").exists());
+        assert_eq!(
+            stashed(mods_path, &folders(&["A", "B"])),
+            vec![disabled_path(mods_path, "A")]
+        );
+    }
+
+    #[test]
+    fn restore_moves_nothing_if_a_folder_is_taken() {
+        let temp = mods(&["A", "B"]);
+        let mods_path = temp.path();
+        stash(mods_path, &folders(&["A", "B"])).unwrap();
+        create_dir_all(mods_path.join("B")).unwrap();
+
+        let e = restore(mods_path, &folders(&["A", "B"])).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert!(!mods_path.join("A").exists());
+        assert!(disabled_path(mods_path, "A").exists());
+    }
+
+    #[test]
+    fn restore_moves_nothing_if_a_copy_is_missing() {
+        let temp = mods(&["A", "B"]);
+        let mods_path = temp.path();
+        stash(mods_path, &folders(&["A", "B"])).unwrap();
+        remove_dir_all(disabled_path(mods_path, "B")).unwrap();
+
+        let e = restore(mods_path, &folders(&["A", "B"])).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert!(!mods_path.join("A").exists());
+        assert!(disabled_path(mods_path, "A").exists());
+    }
+
+    #[test]
+    fn discard_deletes_disabled_copies() {
+        let temp = mods(&["A", "B"]);
+        let mods_path = temp.path();
+        stash(mods_path, &folders(&["A"])).unwrap();
+        discard(mods_path, &folders(&["A", "Missing"])).unwrap();
+        assert!(!disabled_path(mods_path, "A").exists());
+        assert!(mods_path.join("B").exists());
+    }
+}
@@ -1,15 +1,26 @@
-use core::panic;
+use crate::error::{Context, Error, Result};
+use crate::logging::{debug, info};
+use crate::notify::Notifier;
+use crate::store::partial_path;
+use eframe::egui;
 use futures_util::StreamExt;
 use interprocess::local_socket::LocalSocketListener;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
+use std::fs::{create_dir_all, File};
+use std::future::Future;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::SyncSender;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::TrySendError::Disconnected;
+use std::sync::mpsc::{channel, Receiver, Sender, SyncSender};
+use std::sync::{Arc, RwLock};
 use std::thread;
+use std::time::Duration;
+use tokio::runtime::Handle;
 
 pub const BASE_URI: &str = "api.nexusmods.com/v1/games/";
+const API_URI: &str = "api.nexusmods.com/v1/";
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct Links(Vec<DownloadLink>);
@@ -22,14 +33,82 @@ struct DownloadLink {
     uri: String,
 }
 
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+pub enum UpdateCheck {
+    Never,
+    /// Every time SDMM starts
+    OnStartup,
+    #[default]
+    Daily,
+    Weekly,
+}
+
+impl UpdateCheck {
+    /// Whether a check is due, given when the last one ran and if one already ran this session
+    pub fn due(&self, last_check: u64, now: u64, checked: bool) -> bool {
+        match self {
+            UpdateCheck::Never => false,
+            UpdateCheck::OnStartup => !checked,
+            UpdateCheck::Daily => now.saturating_sub(last_check) >= 24 * 60 * 60,
+            UpdateCheck::Weekly => now.saturating_sub(last_check) >= 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Settings the download thread reads for every request, so changes apply without a restart
+pub struct DownloadSettings {
+    pub api_key: String,
+    pub download_path: PathBuf,
+    /// How many downloads may run at the same time
+    pub concurrency: usize,
+}
+
+pub type SharedSettings = Arc<RwLock<DownloadSettings>>;
+
+/// Hands nxm links to the download runtime
+#[derive(Clone)]
+pub struct Downloader {
+    runtime: Handle,
+    sync_sender: SyncSender<(String, usize, usize, usize, usize)>,
+    notifier: Notifier,
+    settings: SharedSettings,
+    /// Downloads currently holding a slot
+    active: Arc<AtomicUsize>,
+}
+
+impl Downloader {
+    /// Downloads the file of an nxm link once a slot is free.
+    ///
+    /// Premium accounts may leave out the link's `key` and `expires`, which allows downloading
+    /// `nxm://stardewvalley/mods/<mod_id>/files/<file_id>` without going through the website.
+    pub fn start(&self, nxm: String) {
+        let (sync_sender, notifier) = (self.sync_sender.clone(), self.notifier.clone());
+        let (settings, active) = (self.settings.clone(), self.active.clone());
+        self.runtime.spawn(async move {
+            if let Err(e) = request_download(&nxm, sync_sender, settings, active).await {
+                let (mod_id, file_id) = get_ids(&nxm);
+                notifier.error(Error::Context(
+                    format!("Failed to download mod {mod_id} file {file_id}"),
+                    Box::new(e),
+                ));
+            }
+        });
+    }
+
+    pub fn runtime(&self) -> Handle {
+        self.runtime.clone()
+    }
+}
+
+/// Starts the download runtime and listens for nxm links sent by other SDMM processes.
+///
+/// Errors of the listener and of every download are sent to `notifier`.
 pub fn handle_download_requests(
     sync_sender: SyncSender<(String, usize, usize, usize, usize)>,
-    download_path: PathBuf,
-    api_key: String,
+    notifier: Notifier,
+    settings: SharedSettings,
     last_download: String,
-) {
-    let base_path = Path::new(BASE_URI);
-    let listener = LocalSocketListener::bind("/tmp/sdmm.sock").unwrap();
+) -> Downloader {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(4)
         .thread_name("download_handler")
@@ -37,216 +116,361 @@ pub fn handle_download_requests(
         .enable_io()
         .enable_time()
         .build()
-        .unwrap();
+        .expect("Failed to start the download runtime");
+    let downloader = Downloader {
+        runtime: runtime.handle().clone(),
+        sync_sender,
+        notifier: notifier.clone(),
+        settings,
+        active: Arc::new(AtomicUsize::new(0)),
+    };
+    let listening = downloader.clone();
     thread::spawn(move || {
+        // Owning the runtime here keeps it running for as long as the app
+        let _runtime = runtime;
         if !last_download.is_empty() {
-            let download_path = download_path.clone();
-            let sync_sender = sync_sender.clone();
-            // Get sent URL string
-            let api_key = api_key.clone();
-            runtime.spawn(async move {
-                let sync_sender = sync_sender.clone();
-                let client = reqwest::Client::new();
-                // Make a request to get the Download URL
-                let (mod_id, file_id) = get_ids(&last_download);
-                let response = client
-                    .get(get_download_url(base_path, &last_download))
-                    // This needs to be less static and with a proper API key from Nexus for
-                    // the application
-                    .header("apikey", api_key)
-                    .send()
-                    .await;
-                match response {
-                    Ok(resp) => {
-                        let response_body = resp.json::<Links>().await;
-                        match response_body {
-                            Ok(links) => {
-                                let download = links.0.first().unwrap();
-                                println!("Beginning Download from {:?}", download.uri);
-                                download_file(
-                                    &client,
-                                    sync_sender,
-                                    download,
-                                    &download_path,
-                                    mod_id,
-                                    file_id,
-                                )
-                                .await;
-                                println!("Finished Download of {}", get_filename(&download.uri));
-                            }
-                            Err(e) => eprintln!("Error Occured: {}", e),
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error Occured: {}", e);
-                    }
-                }
-            });
+            listening.start(last_download);
         }
-        for stream in listener.incoming() {
-            if let Err(e) = &stream {
-                eprintln!("Stream Error: {e}");
+        let listener = match LocalSocketListener::bind("/tmp/sdmm.sock") {
+            Ok(listener) => {
+                info!("Listening for nxm links");
+                listener
             }
-            let mut stream = stream.unwrap();
-            let download_path = download_path.clone();
-            let sync_sender = sync_sender.clone();
+            Err(e) => {
+                notifier.error(Error::Context(
+                    "Failed to listen for nxm links, downloads from the browser will not work"
+                        .into(),
+                    Box::new(e.into()),
+                ));
+                // Keep the runtime alive for the downloads and requests already handed to it
+                loop {
+                    thread::park();
+                }
+            }
+        };
+        for stream in listener.incoming() {
             // Get sent URL string
             let mut buffer = vec![0u8; 1024];
-            stream.read_exact(&mut buffer).unwrap();
-            let api_key = api_key.clone();
-            runtime.spawn(async move {
-                let sync_sender = sync_sender.clone();
-                let client = reqwest::Client::new();
-                let mut string = String::from_utf8(buffer).unwrap();
-                string = string.replace('\0', "");
-                // Make a request to get the Download URL
-                let (mod_id, file_id) = get_ids(&string);
-                let response = client
-                    .get(get_download_url(base_path, &string))
-                    // This needs to be less static and with a proper API key from Nexus for
-                    // the application
-                    .header("apikey", api_key)
-                    .send()
-                    .await;
-                match response {
-                    Ok(resp) => {
-                        let response_body = resp.json::<Links>().await;
-                        match response_body {
-                            Ok(links) => {
-                                let download = links.0.first().unwrap();
-                                println!("Beginning Download from {:?}", download.uri);
-                                download_file(
-                                    &client,
-                                    sync_sender,
-                                    download,
-                                    &download_path,
-                                    mod_id,
-                                    file_id,
-                                )
-                                .await;
-                                println!("Finished Download of {}", get_filename(&download.uri));
-                            }
-                            Err(e) => eprintln!("Error Occured: {}", e),
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error Occured: {}", e);
-                    }
-                }
-            });
+            let read = stream.and_then(|mut stream| stream.read_exact(&mut buffer));
+            if let Err(e) = read {
+                notifier.error(Error::Context(
+                    "Failed to receive nxm link".into(),
+                    Box::new(e.into()),
+                ));
+                continue;
+            }
+            let string = String::from_utf8_lossy(&buffer).replace('\0', "");
+            // The link carries a download key, keep it out of the log
+            let (mod_id, file_id) = get_ids(&string);
+            info!("Received an nxm link for mod {mod_id} file {file_id}");
+            listening.start(string);
         }
     });
+    downloader
 }
 
-async fn download_file(
-    client: &reqwest::Client,
+/// Resolves an nxm link into its download URL and downloads it once a download slot is free
+async fn request_download(
+    nxm: &str,
     sync_sender: SyncSender<(String, usize, usize, usize, usize)>,
-    download: &DownloadLink,
-    download_path: &Path,
-    mod_id: usize,
-    file_id: usize,
-) {
-    let download_clone = download.clone();
-    // let download_path_clone = download_path.clone();
-    let client = client.clone();
-    let sync_sender = sync_sender.clone();
-    let res = client.get(&download_clone.uri).send().await;
-    if let Ok(resp) = res {
-        let total_size = if let Some(size) = resp.content_length() {
-            size as usize
-        } else {
-            eprintln!("Failed to download from {}", &download_clone.uri);
-            0
-        };
-        if total_size == 0 {
-            eprintln!("File size is 0");
-            return;
+    settings: SharedSettings,
+    active: Arc<AtomicUsize>,
+) -> Result<()> {
+    loop {
+        let limit = settings.read().unwrap().concurrency.max(1);
+        if active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .is_ok()
+        {
+            break;
         }
-        let file_name = get_filename(&download_clone.uri);
-        if let Ok(mut file) = File::create(download_path.join(&file_name)) {
-            let mut downloaded: usize = 0;
-            match sync_sender.try_send((
-                file_name.to_string(),
-                downloaded,
-                total_size,
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    let (api_key, download_path) = {
+        let settings = settings.read().unwrap();
+        (settings.api_key.clone(), settings.download_path.clone())
+    };
+    let result = async {
+        let client = reqwest::Client::new();
+        // Make a request to get the Download URL
+        let (mod_id, file_id) = get_ids(nxm);
+        let url = get_download_url(Path::new(BASE_URI), nxm).ok_or_else(|| {
+            // Without the query, which holds the link's download key
+            let link = nxm.split('?').next().unwrap_or_default();
+            Error::Api(format!("{link} is not a valid nxm link"))
+        })?;
+        let links = client
+            .get(url)
+            // This needs to be less static and with a proper API key from Nexus for
+            // the application
+            .header("apikey", api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Links>()
+            .await?;
+        let download = links
+            .0
+            .first()
+            .ok_or_else(|| Error::Api("no download links were returned".into()))?;
+        info!("Downloading mod {mod_id} file {file_id}");
+        download_file(
+            &client,
+            sync_sender,
+            download,
+            &download_path,
+            mod_id,
+            file_id,
+        )
+        .await
+    }
+    .await;
+    active.fetch_sub(1, Ordering::SeqCst);
+    result
+}
+
+/// Mod information fetched from NexusMods in the background
+pub enum Metadata {
+    /// File id of a mod saved before SDMM tracked them, `None` if no file matched
+    FileId {
+        mod_id: u64,
+        zip_name: String,
+        file_id: Option<u64>,
+    },
+    /// Details of a finished download
+    Details {
+        mod_id: u64,
+        file_id: u64,
+        details: Result<Box<(ModDetails, ModFileDetails)>>,
+    },
+    /// Latest version of every mod that could be looked up
+    LatestVersions(Vec<(u64, String)>),
+    /// Whether the API key belongs to a premium account, which may download without the website
+    Account { premium: bool },
+}
+
+/// Runs metadata requests on the download runtime, sending each result to the UI and waking it
+#[derive(Clone)]
+pub struct MetadataFetcher {
+    runtime: Handle,
+    sender: Sender<Metadata>,
+    settings: SharedSettings,
+    ctx: egui::Context,
+    notifier: Notifier,
+}
+
+impl MetadataFetcher {
+    pub fn new(
+        runtime: Handle,
+        settings: SharedSettings,
+        ctx: egui::Context,
+        notifier: Notifier,
+    ) -> (MetadataFetcher, Receiver<Metadata>) {
+        let (sender, receiver) = channel();
+        let fetcher = MetadataFetcher {
+            runtime,
+            sender,
+            settings,
+            ctx,
+            notifier,
+        };
+        (fetcher, receiver)
+    }
+
+    pub fn file_id(&self, mod_id: u64, zip_name: String, version: String) {
+        let notifier = self.notifier.clone();
+        self.spawn(move |client: reqwest::Client, api_key: String| async move {
+            #[derive(Deserialize)]
+            struct ModFiles {
+                files: Vec<ModFileDetails>,
+            }
+            let files = get_json::<ModFiles>(
+                &client,
+                &api_key,
+                &format!("games/stardewvalley/mods/{mod_id}/files.json"),
+            )
+            .await;
+            let file_id = match files {
+                Ok(files) => files
+                    .files
+                    .into_iter()
+                    .find(|f| {
+                        f.file_name.as_deref() == Some(zip_name.as_str())
+                            && f.version.as_deref() == Some(version.as_str())
+                    })
+                    .and_then(|f| f.file_id),
+                Err(e) => {
+                    notifier.warning(format!("Failed to get files of mod {mod_id}: {e}"));
+                    None
+                }
+            };
+            Metadata::FileId {
                 mod_id,
+                zip_name,
                 file_id,
-            )) {
-                Err(e)
-                    if e == Disconnected((
-                        file_name.to_string(),
-                        downloaded,
-                        total_size,
-                        mod_id,
-                        file_id,
-                    )) =>
-                {
-                    panic!("Error Occured when sending: {}", e);
+            }
+        });
+    }
+
+    pub fn details(&self, mod_id: u64, file_id: u64) {
+        self.spawn(move |client: reqwest::Client, api_key: String| async move {
+            let mod_details = get_json::<ModDetails>(
+                &client,
+                &api_key,
+                &format!("games/stardewvalley/mods/{mod_id}.json"),
+            )
+            .await;
+            let file_details = get_json::<ModFileDetails>(
+                &client,
+                &api_key,
+                &format!("games/stardewvalley/mods/{mod_id}/files/{file_id}.json"),
+            )
+            .await;
+            let details = match (mod_details, file_details) {
+                (Ok(mod_details), Ok(file_details)) => Ok(Box::new((mod_details, file_details))),
+                (Err(e), _) | (_, Err(e)) => {
+                    Err(e).context(|| format!("Failed to get the details of mod {mod_id}"))
                 }
-                _ => {}
+            };
+            Metadata::Details {
+                mod_id,
+                file_id,
+                details,
             }
-            let mut stream = resp.bytes_stream();
-            while let Some(item) = stream.next().await {
-                if let Ok(chunk) = item {
-                    let written = file.write(&chunk).unwrap();
-                    assert_eq!(written, chunk.len());
-                    downloaded += chunk.len();
-                    match sync_sender.try_send((
-                        file_name.to_string(),
-                        downloaded,
-                        total_size,
-                        mod_id,
-                        file_id,
-                    )) {
-                        Err(e)
-                            if e == Disconnected((
-                                file_name.to_string(),
-                                downloaded,
-                                total_size,
-                                mod_id,
-                                file_id,
-                            )) =>
-                        {
-                            panic!("Error Occured when sending: {}", e);
-                        }
-                        _ => {}
+        });
+    }
+
+    pub fn latest_versions(&self, mod_ids: Vec<u64>) {
+        let notifier = self.notifier.clone();
+        self.spawn(move |client: reqwest::Client, api_key: String| async move {
+            let mut versions = vec![];
+            for mod_id in mod_ids {
+                let details = get_json::<ModDetails>(
+                    &client,
+                    &api_key,
+                    &format!("games/stardewvalley/mods/{mod_id}.json"),
+                )
+                .await;
+                match details {
+                    Ok(details) => versions.push((mod_id, details.version)),
+                    Err(e) => {
+                        notifier.warning(format!("Failed to check mod {mod_id} for updates: {e}"))
                     }
-                } else {
-                    eprintln!(
-                        "Failed to create file at {}",
-                        download_path.join(file_name).display()
-                    );
-                    return;
                 }
             }
-            sync_sender
-                .send((
-                    file_name.to_string(),
-                    downloaded,
-                    total_size,
-                    mod_id,
-                    file_id,
-                ))
-                .unwrap();
-        } else {
-            eprintln!(
-                "Failed to create file at {}",
-                download_path.join(file_name).display()
-            );
+            Metadata::LatestVersions(versions)
+        });
+    }
+
+    pub fn account(&self) {
+        let notifier = self.notifier.clone();
+        self.spawn(move |client: reqwest::Client, api_key: String| async move {
+            #[derive(Deserialize)]
+            struct User {
+                is_premium: bool,
+            }
+            let premium = match get_json::<User>(&client, &api_key, "users/validate.json").await {
+                Ok(user) => user.is_premium,
+                Err(e) => {
+                    notifier.warning(format!("Failed to check the NexusMods account: {e}"));
+                    false
+                }
+            };
+            Metadata::Account { premium }
+        });
+    }
+
+    fn spawn<F, Fut>(&self, request: F)
+    where
+        F: FnOnce(reqwest::Client, String) -> Fut + Send + 'static,
+        Fut: Future<Output = Metadata> + Send + 'static,
+    {
+        let api_key = self.settings.read().unwrap().api_key.clone();
+        let sender = self.sender.clone();
+        let ctx = self.ctx.clone();
+        self.runtime.spawn(async move {
+            let metadata = request(reqwest::Client::new(), api_key).await;
+            let _ = sender.send(metadata);
+            ctx.request_repaint();
+        });
+    }
+}
+
+async fn get_json<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    api_key: &str,
+    path: &str,
+) -> Result<T> {
+    debug!("GET {path}");
+    Ok(client
+        .get(format!("https://{API_URI}{path}"))
+        .header("apikey", api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<T>()
+        .await?)
+}
+
+async fn download_file(
+    client: &reqwest::Client,
+    sync_sender: SyncSender<(String, usize, usize, usize, usize)>,
+    download: &DownloadLink,
+    download_path: &Path,
+    mod_id: usize,
+    file_id: usize,
+) -> Result<()> {
+    let resp = client.get(&download.uri).send().await?.error_for_status()?;
+    let total_size = match resp.content_length() {
+        Some(size) if size > 0 => size as usize,
+        _ => return Err(Error::Api(format!("{} has no file size", download.uri))),
+    };
+    let file_name = get_filename(&download.uri)
+        .ok_or_else(|| Error::Api(format!("no file name in {}", download.uri)))?;
+    let partial = partial_path(download_path, mod_id, file_id, &file_name);
+    if let Some(parent) = partial.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut file =
+        File::create(&partial).context(|| format!("Failed to create {}", partial.display()))?;
+    let mut downloaded: usize = 0;
+    let progress = |downloaded| {
+        (
+            file_name.to_string(),
+            downloaded,
+            total_size,
+            mod_id,
+            file_id,
+        )
+    };
+    // Nobody is listening anymore once the window was closed
+    if let Err(Disconnected(_)) = sync_sender.try_send(progress(downloaded)) {
+        return Ok(());
+    }
+    let mut stream = resp.bytes_stream();
+    while let Some(item) = stream.next().await {
+        let chunk = item?;
+        file.write_all(&chunk)?;
+        downloaded += chunk.len();
+        if let Err(Disconnected(_)) = sync_sender.try_send(progress(downloaded)) {
+            return Ok(());
         }
-    } else {
-        eprintln!("Failed to download from {}", &download_clone.uri);
     }
+    let _ = sync_sender.send(progress(downloaded));
+    info!("Finished downloading {file_name}");
+    Ok(())
 }
 
-fn get_filename(uri: &str) -> String {
+fn get_filename(uri: &str) -> Option<String> {
     let split_uri = uri.split('/').collect::<Vec<&str>>();
-    let file_name = if split_uri[2].contains("nexus-cdn") {
-        split_uri[5]
+    let file_name = if split_uri.get(2)?.contains("nexus-cdn") {
+        split_uri.get(5)?
     } else {
-        split_uri[6]
+        split_uri.get(6)?
     };
-    file_name.split('?').next().unwrap().to_string()
+    file_name.split('?').next().map(str::to_string)
 }
 
 fn get_ids(requested_uri: &str) -> (usize, usize) {
@@ -263,17 +487,15 @@ fn get_ids(requested_uri: &str) -> (usize, usize) {
 }
 
 // Convert URL String in Path, excluding the nxm:// part
-fn get_download_url(base_path: &Path, requested_uri: &str) -> String {
-    let url = requested_uri.split_at(6).1;
-    let url: Vec<&str> = url.split('?').collect();
-    let path = Path::new(&url[0]);
-    let queries = url[1];
-    format!(
+fn get_download_url(base_path: &Path, requested_uri: &str) -> Option<String> {
+    let url = requested_uri.strip_prefix("nxm://")?;
+    let (path, queries) = url.split_once('?').unwrap_or((url, ""));
+    Some(format!(
         "https://{}{}/download_link.json?{}",
         base_path.display(),
-        path.display(),
-        &queries
-    )
+        Path::new(path).display(),
+        queries
+    ))
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -0,0 +1,109 @@
+use crate::smapi::PLATFORM;
+use crate::state::STATE_VERSION;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Archive(zip::result::ZipError),
+    Network(reqwest::Error),
+    /// NexusMods answered, but not with what SDMM asked for
+    Api(String),
+    /// The state file is not valid JSON or does not match the schema
+    State(serde_json::Error),
+    /// The state file was written by a newer SDMM
+    StateTooNew(u64),
+    /// The archive has no `internal/<platform>/install.dat`, so it is not a SMAPI installer
+    NotSmapiInstaller,
+    /// A shared mod list or lockfile could not be understood
+    InvalidModList(String),
+    /// A mod pack could not be understood or its archives are damaged
+    InvalidPack(String),
+    /// Two units chosen from one archive would be installed into the same folder
+    SameFolder(String),
+    /// The game path does not contain the given game file
+    MissingGameFile(PathBuf),
+    /// What SDMM was doing when the error happened
+    Context(String, Box<Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Archive(e) => write!(f, "invalid archive: {e}"),
+            Error::Network(e) => write!(f, "request failed: {e}"),
+            Error::Api(message) => write!(f, "NexusMods: {message}"),
+            Error::State(e) => write!(f, "invalid state file: {e}"),
+            Error::StateTooNew(version) => write!(
+                f,
+                "state file version {version} is newer than the supported {STATE_VERSION}"
+            ),
+            Error::NotSmapiInstaller => write!(
+                f,
+                "archive is not a SMAPI installer, internal/{PLATFORM}/install.dat is missing"
+            ),
+            Error::InvalidModList(reason) => write!(f, "invalid mod list: {reason}"),
+            Error::InvalidPack(reason) => write!(f, "invalid pack: {reason}"),
+            Error::SameFolder(folder) => write!(
+                f,
+                "more than one of the chosen parts installs into {folder}, pick only one of them"
+            ),
+            Error::MissingGameFile(path) => {
+                write!(f, "{} not found, is the game path correct?", path.display())
+            }
+            Error::Context(context, e) => write!(f, "{context}: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Archive(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        // Download URLs carry the key and expiry of the nxm link, errors end up in the log
+        Error::Network(e.without_url())
+    }
+}
+
+/// Describes what failed, e.g. `.context(|| format!("Failed to install {name}"))`
+pub trait Context<T> {
+    fn context(self, context: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|e| Error::Context(context(), Box::new(e.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_errors_leave_out_the_url() {
+        let e = reqwest::blocking::Client::new()
+            .get("http://127.0.0.1:1/download_link.json?key=secret&expires=1")
+            .send()
+            .unwrap_err();
+        assert!(e.to_string().contains("secret"));
+        let message = Error::from(e).to_string();
+        assert!(message.starts_with("request failed"));
+        assert!(!message.contains("secret"));
+    }
+}
@@ -0,0 +1,312 @@
+use directories_next::BaseDirs;
+use std::cmp::Ordering;
+use std::fs::{read, read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+pub const GAME_DLL: &str = "Stardew Valley.dll";
+pub const SMAPI_DLL: &str = "StardewModdingAPI.dll";
+
+#[derive(Default, Clone)]
+pub struct Versions {
+    pub game: Option<String>,
+    pub smapi: Option<String>,
+}
+
+impl Versions {
+    pub fn detect(game_path: &Path) -> Versions {
+        Versions {
+            game: product_version(&game_path.join(GAME_DLL)),
+            smapi: product_version(&game_path.join(SMAPI_DLL)),
+        }
+    }
+}
+
+/// Compares dotted versions like `3.18.2` numerically, ignoring any prerelease or build suffix
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .split(|c| c == '-' || c == '+')
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.trim().parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for i in 0..a.len().max(b.len()) {
+        match a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)) {
+            Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Reads the product version from the version resource of a .NET assembly.
+///
+/// The resource stores each value as UTF-16 after its key, padded to a 32 bit boundary.
+fn product_version(path: &Path) -> Option<String> {
+    let bytes = read(path).ok()?;
+    ["ProductVersion", "FileVersion"]
+        .iter()
+        .find_map(|key| version_value(&bytes, key))
+        .map(|version| version.split('+').next().unwrap_or_default().to_string())
+}
+
+fn version_value(bytes: &[u8], key: &str) -> Option<String> {
+    let mut needle: Vec<u8> = key.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    needle.extend([0, 0]);
+    let start = bytes
+        .windows(needle.len())
+        .position(|window| window == needle.as_slice())?;
+    let mut offset = start + needle.len();
+    offset += (4 - offset % 4) % 4;
+    let value: Vec<u16> = bytes
+        .get(offset..)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|c| *c != 0)
+        .collect();
+    let value = String::from_utf16(&value).ok()?;
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Looks for the game in the Steam libraries and the usual GOG install locations
+pub fn detect_game_path() -> Option<PathBuf> {
+    candidate_paths()
+        .into_iter()
+        .find(|path| is_game_path(path))
+}
+
+/// Whether `path` is a game folder, which always has the game assembly next to the executable
+pub fn is_game_path(path: &Path) -> bool {
+    path.join(GAME_DLL).is_file()
+}
+
+fn candidate_paths() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = vec![];
+    for steam in steam_roots() {
+        for library in steam_libraries(&steam) {
+            let common = library
+                .join("steamapps")
+                .join("common")
+                .join("Stardew Valley");
+            candidates.push(common.join("Contents").join("MacOS"));
+            candidates.push(common);
+            // GOG copies added to Steam as non-Steam games run inside their own Proton prefix
+            if let Ok(prefixes) = read_dir(library.join("steamapps").join("compatdata")) {
+                for prefix in prefixes.flatten() {
+                    candidates.extend(gog_paths(&prefix.path().join("pfx").join("drive_c")));
+                }
+            }
+        }
+    }
+    candidates.extend(gog_registry_path());
+    #[cfg(target_os = "windows")]
+    candidates.extend(gog_paths(Path::new("C:\\")));
+    if let Some(dirs) = BaseDirs::new() {
+        let home = dirs.home_dir();
+        candidates.push(home.join("GOG Games").join("Stardew Valley").join("game"));
+        candidates.extend(gog_paths(&home.join(".wine").join("drive_c")));
+    }
+    #[cfg(target_os = "macos")]
+    candidates.push(PathBuf::from(
+        "/Applications/Stardew Valley.app/Contents/MacOS",
+    ));
+    candidates
+}
+
+/// Where GOG installs the game inside a Windows drive
+fn gog_paths(drive: &Path) -> [PathBuf; 3] {
+    [
+        drive.join("GOG Games").join("Stardew Valley"),
+        drive
+            .join("Program Files (x86)")
+            .join("GOG Galaxy")
+            .join("Games")
+            .join("Stardew Valley"),
+        drive
+            .join("Program Files")
+            .join("GOG Galaxy")
+            .join("Games")
+            .join("Stardew Valley"),
+    ]
+}
+
+#[cfg(target_os = "windows")]
+fn gog_registry_path() -> Option<PathBuf> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+    const GOG_GAME_ID: &str = "1453375253";
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let key = hklm
+        .open_subkey(format!(
+            "SOFTWARE\\WOW6432Node\\GOG.com\\Games\\{GOG_GAME_ID}"
+        ))
+        .ok()?;
+    key.get_value::<String, _>("path").ok().map(PathBuf::from)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn gog_registry_path() -> Option<PathBuf> {
+    None
+}
+
+fn steam_roots() -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = vec![];
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::*;
+        use winreg::RegKey;
+        if let Ok(key) = RegKey::predef(HKEY_CURRENT_USER).open_subkey("Software\\Valve\\Steam")
+            && let Ok(path) = key.get_value::<String, _>("SteamPath")
+        {
+            roots.push(PathBuf::from(path));
+        }
+        roots.push(PathBuf::from("C:\\Program Files (x86)\\Steam"));
+    }
+    #[cfg(target_os = "macos")]
+    if let Some(dirs) = BaseDirs::new() {
+        roots.push(dirs.home_dir().join("Library/Application Support/Steam"));
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    if let Some(dirs) = BaseDirs::new() {
+        let home = dirs.home_dir();
+        roots.push(home.join(".steam/steam"));
+        roots.push(home.join(".local/share/Steam"));
+        // Flatpak Steam keeps its data inside the sandbox
+        roots.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+    }
+    // The same install is often reachable through several symlinks
+    let mut seen: Vec<PathBuf> = vec![];
+    roots.retain(|root| match root.canonicalize() {
+        Ok(path) if !seen.contains(&path) => {
+            seen.push(path);
+            true
+        }
+        _ => false,
+    });
+    roots
+}
+
+/// The Steam install itself plus every library listed in its `libraryfolders.vdf`
+fn steam_libraries(steam: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam.to_path_buf()];
+    let vdf = read_to_string(steam.join("steamapps").join("libraryfolders.vdf"))
+        .or_else(|_| read_to_string(steam.join("config").join("libraryfolders.vdf")))
+        .unwrap_or_default();
+    for path in library_paths(&vdf) {
+        if !libraries.contains(&path) {
+            libraries.push(path);
+        }
+    }
+    libraries
+}
+
+/// The library folders listed in a `libraryfolders.vdf`
+fn library_paths(vdf: &str) -> Vec<PathBuf> {
+    vdf.lines()
+        .filter_map(|line| {
+            // Entries look like `"path"		"D:\\SteamLibrary"`
+            let mut fields = line.split('"').skip(1).step_by(2);
+            match (fields.next(), fields.next()) {
+                (Some("path"), Some(path)) => Some(PathBuf::from(path.replace("\\\\", "\\"))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert_eq!(compare_versions("3.18.2", "3.18.2"), Ordering::Equal);
+        assert_eq!(compare_versions("3.9.0", "3.18.0"), Ordering::Less);
+        assert_eq!(compare_versions("4.0", "3.18.6"), Ordering::Greater);
+        assert_eq!(compare_versions("1.6", "1.6.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.6.1", "1.6"), Ordering::Greater);
+    }
+
+    #[test]
+    fn version_suffixes_are_ignored() {
+        assert_eq!(compare_versions("4.0.0-beta.1", "4.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("3.18.2+abc123", "3.18.2"), Ordering::Equal);
+        assert_eq!(compare_versions("1.5.6.22018", "1.5.6"), Ordering::Greater);
+    }
+
+    #[test]
+    fn reads_library_folders() {
+        let vdf = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/user/.local/share/Steam"
+		"label"		""
+		"contentid"		"2818162286843428213"
+		"totalsize"		"0"
+		"apps"
+		{
+			"228980"		"411922010"
+		}
+	}
+	"1"
+	{
+		"path"		"D:\\SteamLibrary"
+		"label"		"Games"
+		"apps"
+		{
+			"413150"		"1038473123"
+		}
+	}
+}
+"#;
+        assert_eq!(
+            library_paths(vdf),
+            vec![
+                PathBuf::from("/home/user/.local/share/Steam"),
+                PathBuf::from("D:\\SteamLibrary")
+            ]
+        );
+        assert!(library_paths("").is_empty());
+    }
+
+    /// A version resource entry as it appears in the assembly, padded to 32 bits
+    fn resource(key: &str, value: &str) -> Vec<u8> {
+        let utf16 = |s: &str| -> Vec<u8> { s.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let mut bytes = vec![0x30, 0x00, 0x01, 0x00];
+        bytes.extend(utf16(key));
+        bytes.extend([0, 0]);
+        bytes.resize(bytes.len() + (4 - bytes.len() % 4) % 4, 0);
+        bytes.extend(utf16(value));
+        bytes.extend([0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn reads_version_resource_values() {
+        let mut bytes = b"MZ\x90\x00".to_vec();
+        bytes.extend(resource("FileVersion", "1.6.8.24119"));
+        bytes.extend(resource("ProductVersion", "1.6.8+1f7a2b"));
+        assert_eq!(
+            version_value(&bytes, "ProductVersion").as_deref(),
+            Some("1.6.8+1f7a2b")
+        );
+        assert_eq!(
+            version_value(&bytes, "FileVersion").as_deref(),
+            Some("1.6.8.24119")
+        );
+        assert_eq!(version_value(&bytes, "Comments"), None);
+        assert_eq!(
+            version_value(&resource("ProductVersion", ""), "ProductVersion"),
+            None
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Steam app id of Stardew Valley
+const STEAM_APP_ID: u32 = 413150;
+/// Console lines kept in memory, older lines are dropped
+const MAX_OUTPUT_LINES: usize = 10_000;
+
+#[cfg(target_os = "windows")]
+const SMAPI_EXECUTABLE: &str = "StardewModdingAPI.exe";
+#[cfg(not(target_os = "windows"))]
+const SMAPI_EXECUTABLE: &str = "StardewModdingAPI";
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+pub enum LaunchMethod {
+    /// Start SMAPI from the game folder and capture its console
+    #[default]
+    Direct,
+    /// Let Steam start the game, which runs SMAPI if the launch options are set up for it
+    Steam,
+}
+
+pub enum GameEvent {
+    Output(String),
+    Exited(Option<i32>),
+}
+
+/// A game started from SDMM
+pub struct GameProcess {
+    receiver: Option<Receiver<GameEvent>>,
+    pub output: VecDeque<String>,
+    /// Set once the process ended, holding its exit code if it had one
+    pub exited: Option<Option<i32>>,
+}
+
+impl GameProcess {
+    /// Whether SDMM can see the console and exit of the game, which is not the case through Steam
+    pub fn tracked(&self) -> bool {
+        self.receiver.is_some() || self.exited.is_some()
+    }
+
+    pub fn running(&self) -> bool {
+        self.receiver.is_some() && self.exited.is_none()
+    }
+
+    /// The game ended with a failure exit code
+    pub fn crashed(&self) -> bool {
+        matches!(self.exited, Some(Some(code)) if code != 0)
+    }
+
+    /// Collects new output and the exit status, returns whether anything changed
+    pub fn poll(&mut self) -> bool {
+        let Some(receiver) = &self.receiver else {
+            return false;
+        };
+        let mut changed = false;
+        for event in receiver.try_iter() {
+            changed = true;
+            match event {
+                GameEvent::Output(line) => {
+                    if self.output.len() == MAX_OUTPUT_LINES {
+                        self.output.pop_front();
+                    }
+                    self.output.push_back(line);
+                }
+                GameEvent::Exited(code) => self.exited = Some(code),
+            }
+        }
+        changed
+    }
+}
+
+/// Starts the game, `ctx` is woken up whenever the game writes output or exits
+pub fn launch(
+    game_path: &Path,
+    method: LaunchMethod,
+    args: &str,
+    ctx: &egui::Context,
+) -> io::Result<GameProcess> {
+    if method == LaunchMethod::Steam {
+        open(&format!("steam://rungameid/{STEAM_APP_ID}"))?;
+        return Ok(GameProcess {
+            receiver: None,
+            output: VecDeque::new(),
+            exited: None,
+        });
+    }
+    let mut child = Command::new(game_path.join(SMAPI_EXECUTABLE))
+        .args(args.split_whitespace())
+        .current_dir(game_path)
+        // The unix launcher would otherwise open its own terminal window
+        .env("SMAPI_NO_TERMINAL", "true")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (sender, receiver) = channel();
+    let mut readers = vec![];
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward_lines(stdout, sender.clone(), ctx.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward_lines(stderr, sender.clone(), ctx.clone()));
+    }
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let code = child.wait().ok().and_then(|status| status.code());
+        // The last lines are still on their way once the process is gone
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let _ = sender.send(GameEvent::Exited(code));
+        ctx.request_repaint();
+    });
+    Ok(GameProcess {
+        receiver: Some(receiver),
+        output: VecDeque::new(),
+        exited: None,
+    })
+}
+
+/// Opens a URL or path with whatever the system has registered for it
+pub fn open(target: &str) -> io::Result<()> {
+    // Not `cmd /C start`, cmd would end the URL at the first `&` and run the rest as a command
+    #[cfg(target_os = "windows")]
+    let mut command = Command::new("explorer");
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+    command.arg(target).spawn().map(|_| ())
+}
+
+fn forward_lines(
+    stream: impl Read + Send + 'static,
+    sender: Sender<GameEvent>,
+    ctx: egui::Context,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(GameEvent::Output(line)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+                Err(_) => break,
+            }
+        }
+    })
+}
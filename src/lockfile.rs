@@ -0,0 +1,275 @@
+use crate::app::GameMod;
+use crate::error::{Error, Result};
+use crate::modlist::Entry;
+use crate::receipt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+/// Version of the lockfile format this build writes
+pub const LOCKFILE_VERSION: u64 = 1;
+
+/// The exact archives and install choices of a set of active mods
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Lockfile {
+    pub version: u64,
+    pub mods: Vec<Locked>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Locked {
+    #[serde(flatten)]
+    pub entry: Entry,
+    /// Hash of the archive, which has to match exactly
+    pub hash: String,
+    /// Units picked for install, empty when the archive only has one
+    #[serde(default)]
+    pub units: Vec<String>,
+}
+
+impl Locked {
+    pub fn new(r#mod: &GameMod, local_ids: u64) -> Locked {
+        Locked {
+            entry: Entry::new(r#mod, local_ids),
+            hash: r#mod.hash.clone(),
+            units: r#mod.units.clone(),
+        }
+    }
+
+    /// Whether `r#mod` is exactly what was locked
+    pub fn is(&self, r#mod: &GameMod) -> bool {
+        !self.hash.is_empty() && self.hash == r#mod.hash && self.units == r#mod.units
+    }
+}
+
+/// A difference between the active mods and a lockfile
+pub enum Drift {
+    /// Neither active nor in the library
+    Missing(Locked),
+    /// In the library but not active
+    Disabled(Locked),
+    /// Active in another version or from another archive
+    Different { locked: Locked, active: String },
+    /// Installed from the locked archive with other units
+    Units(Locked),
+    /// Files of the mod were changed after it was installed
+    Modified { name: String, files: usize },
+    /// Active but not in the lockfile
+    Extra(String),
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Missing(locked) => write!(
+                f,
+                "{} {} is not in the library",
+                locked.entry.name, locked.entry.version
+            ),
+            Drift::Disabled(locked) => write!(
+                f,
+                "{} {} is not active",
+                locked.entry.name, locked.entry.version
+            ),
+            Drift::Different { locked, active } => write!(
+                f,
+                "{} {} is locked but {active} is active",
+                locked.entry.name, locked.entry.version
+            ),
+            Drift::Units(locked) => write!(
+                f,
+                "{} is installed with other parts of its archive",
+                locked.entry.name
+            ),
+            Drift::Modified { name, files } => write!(f, "{name} has {files} changed files"),
+            Drift::Extra(name) => write!(f, "{name} is active but not locked"),
+        }
+    }
+}
+
+/// Locks the given mods, mods installed outside of SDMM have no archive and are left out
+pub fn save(path: &Path, mods: &[GameMod], local_ids: u64) -> Result<()> {
+    let lockfile = Lockfile {
+        version: LOCKFILE_VERSION,
+        mods: mods
+            .iter()
+            .filter(|m| !m.external && !m.hash.is_empty())
+            .map(|m| Locked::new(m, local_ids))
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&lockfile)
+        .map_err(|e| Error::InvalidModList(e.to_string()))?;
+    write(path, json)?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Lockfile> {
+    let lockfile: Lockfile = serde_json::from_str(&read_to_string(path)?)
+        .map_err(|e| Error::InvalidModList(e.to_string()))?;
+    if lockfile.version > LOCKFILE_VERSION {
+        return Err(Error::InvalidModList(format!(
+            "lockfile version {} is newer than the supported {LOCKFILE_VERSION}",
+            lockfile.version
+        )));
+    }
+    Ok(lockfile)
+}
+
+/// Everything that differs between the active mods and the lockfile
+pub fn verify(
+    lockfile: &Lockfile,
+    active: &[GameMod],
+    inactive: &[GameMod],
+    mods_path: &Path,
+) -> Vec<Drift> {
+    let mut drift = vec![];
+    for locked in &lockfile.mods {
+        if let Some(r#mod) = active.iter().find(|m| locked.is(m)) {
+            let files = receipt::modified_files(mods_path, &r#mod.installed).len();
+            if files > 0 {
+                drift.push(Drift::Modified {
+                    name: r#mod.name.clone(),
+                    files,
+                });
+            }
+        } else if active.iter().any(|m| m.hash == locked.hash) {
+            drift.push(Drift::Units(locked.clone()));
+        } else if let Some(r#mod) = active.iter().find(|m| locked.entry.same_mod(m)) {
+            drift.push(Drift::Different {
+                locked: locked.clone(),
+                active: format!("{} {}", r#mod.name, r#mod.version),
+            });
+        } else if inactive.iter().any(|m| m.hash == locked.hash) {
+            drift.push(Drift::Disabled(locked.clone()));
+        } else {
+            drift.push(Drift::Missing(locked.clone()));
+        }
+    }
+    for r#mod in active {
+        let locked = lockfile
+            .mods
+            .iter()
+            .any(|l| l.hash == r#mod.hash || l.entry.same_mod(r#mod));
+        if !locked {
+            drift.push(Drift::Extra(format!("{} {}", r#mod.name, r#mod.version)));
+        }
+    }
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use std::path::PathBuf;
+
+    const LOCAL: u64 = u64::MAX - 10000;
+
+    fn game_mod(name: &str, mod_id: u64, version: &str, hash: &str) -> GameMod {
+        let mut r#mod = GameMod::default();
+        r#mod.name = name.to_string();
+        (r#mod.mod_id, r#mod.file_id) = (mod_id, mod_id);
+        r#mod.version = version.to_string();
+        r#mod.hash = hash.to_string();
+        r#mod
+    }
+
+    fn lock(mods: &[GameMod]) -> Lockfile {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            mods: mods.iter().map(|m| Locked::new(m, LOCAL)).collect(),
+        }
+    }
+
+    #[test]
+    fn locked_mods_match_without_drift() {
+        let temp = tempfile::tempdir().unwrap();
+        let active = [
+            game_mod("CP", 1915, "1.28.0", "a"),
+            game_mod("Lookup", 541, "1.40.0", "b"),
+        ];
+        assert!(verify(&lock(&active), &active, &[], temp.path()).is_empty());
+    }
+
+    #[test]
+    fn verify_reports_every_kind_of_drift() {
+        let temp = tempfile::tempdir().unwrap();
+        let mods_path = temp.path();
+        create_dir_all(mods_path.join("Changed")).unwrap();
+        write(mods_path.join("Changed/manifest.json"), "{}").unwrap();
+
+        let mut changed = game_mod("Changed", 1, "1.0", "changed");
+        changed.installed =
+            receipt::create(mods_path, &[PathBuf::from("Changed/manifest.json")]).unwrap();
+        let mut parts = game_mod("Parts", 2, "1.0", "parts");
+        parts.units = vec![String::from("Parts/A")];
+        let locked = lock(&[
+            changed.clone(),
+            parts.clone(),
+            game_mod("Older", 3, "1.0", "older"),
+            game_mod("Off", 4, "1.0", "off"),
+            game_mod("Gone", 5, "1.0", "gone"),
+        ]);
+        write(
+            mods_path.join("Changed/manifest.json"),
+            "{\"changed\": true}",
+        )
+        .unwrap();
+
+        parts.units = vec![String::from("Parts/B")];
+        let active = [
+            changed,
+            parts,
+            game_mod("Older", 3, "2.0", "newer"),
+            game_mod("Extra", 6, "1.0", "extra"),
+        ];
+        let inactive = [game_mod("Off", 4, "1.0", "off")];
+        let drift: Vec<String> = verify(&locked, &active, &inactive, mods_path)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            drift,
+            vec![
+                "Changed has 1 changed files",
+                "Parts is installed with other parts of its archive",
+                "Older 1.0 is locked but Older 2.0 is active",
+                "Off 1.0 is not active",
+                "Gone 1.0 is not in the library",
+                "Extra 1.0 is active but not locked",
+            ]
+        );
+    }
+
+    #[test]
+    fn local_mods_are_found_by_hash() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip = game_mod("Zip", LOCAL + 1, "1.0", "zip");
+        let locked = lock(&[zip.clone()]);
+        assert_eq!(locked.mods[0].entry.mod_id, 0);
+        // Ids of local mods differ between computers, the archive is what counts
+        let mut here = zip;
+        (here.mod_id, here.file_id) = (LOCAL + 5, LOCAL + 5);
+        assert!(verify(&locked, &[here], &[], temp.path()).is_empty());
+    }
+
+    #[test]
+    fn save_leaves_out_mods_without_archive() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("lock.json");
+        let mut external = game_mod("Hand", 7, "1.0", "hand");
+        external.external = true;
+        let mods = [
+            game_mod("CP", 1915, "1.28.0", "a"),
+            external,
+            game_mod("NoHash", 8, "1.0", ""),
+        ];
+        save(&path, &mods, LOCAL).unwrap();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.mods.len(), 1);
+        assert!(loaded.mods[0].is(&mods[0]));
+        assert_eq!(loaded.mods[0].entry.name, "CP");
+    }
+}
@@ -0,0 +1,147 @@
+use directories_next::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{create_dir_all, rename, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOG_FILE: &str = "sdmm.log";
+/// The log is rotated once it grows past this many bytes
+const MAX_SIZE: u64 = 1024 * 1024;
+/// Rotated logs kept next to the current one, `sdmm.1.log` being the newest
+const KEEP: usize = 3;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Level {
+    Error,
+    Warning,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warning => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        };
+        write!(f, "{name:5}")
+    }
+}
+
+struct Logger {
+    level: AtomicU8,
+    file: Mutex<Option<(File, u64)>>,
+}
+
+/// The folder holding the log files
+pub fn log_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", crate::PROJECT_NAME).map(|dirs| dirs.data_dir().join("logs"))
+}
+
+/// Starts writing to the log file, rotating the previous one if it got too large.
+///
+/// Logging still goes to stderr when the file cannot be opened.
+pub fn init(level: Level) -> io::Result<()> {
+    let logger = LOGGER.get_or_init(|| Logger {
+        level: AtomicU8::new(level as u8),
+        file: Mutex::new(None),
+    });
+    set_level(level);
+    let file = open()?;
+    *logger.file.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+pub fn set_level(level: Level) {
+    if let Some(logger) = LOGGER.get() {
+        logger.level.store(level as u8, Ordering::Relaxed);
+    }
+}
+
+fn enabled(level: Level) -> bool {
+    let max = LOGGER
+        .get()
+        .map_or(Level::Info as u8, |l| l.level.load(Ordering::Relaxed));
+    level as u8 <= max
+}
+
+/// Used by the logging macros, prefer those
+pub fn write(level: Level, target: &str, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0., |d| d.as_secs_f64());
+    let line = format!("{time:.3} {level} {target}: {args}\n");
+    eprint!("{line}");
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    let mut file = logger.file.lock().unwrap();
+    if let Some((_, written)) = file.as_ref()
+        && *written > MAX_SIZE
+    {
+        // Dropping the handle first, Windows refuses to rename open files
+        *file = None;
+        *file = open().ok();
+    }
+    if let Some((log, written)) = file.as_mut()
+        && log.write_all(line.as_bytes()).is_ok()
+    {
+        *written += line.len() as u64;
+    }
+}
+
+fn open() -> io::Result<(File, u64)> {
+    let dir = log_dir().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    create_dir_all(&dir)?;
+    let path = dir.join(LOG_FILE);
+    if path.metadata().map_or(false, |m| m.len() > MAX_SIZE) {
+        for i in (1..KEEP).rev() {
+            let _ = rename(
+                dir.join(format!("sdmm.{i}.log")),
+                dir.join(format!("sdmm.{}.log", i + 1)),
+            );
+        }
+        rename(&path, dir.join("sdmm.1.log"))?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let written = file.metadata()?.len();
+    Ok((file, written))
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::logging::write($crate::logging::Level::Error, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::logging::write($crate::logging::Level::Warning, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logging::write($crate::logging::Level::Info, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::logging::write($crate::logging::Level::Debug, module_path!(), format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {debug, error, info, warning};
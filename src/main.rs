@@ -6,17 +6,37 @@ use std::io;
 use std::io::Write;
 use std::path::PathBuf;
 mod app;
+mod archive;
+mod bisect;
+mod diagnostics;
+mod disable;
 mod download;
+mod error;
+mod game;
+mod launch;
+mod lockfile;
+mod logging;
+mod modlist;
+mod notify;
+mod pack;
+mod receipt;
+mod smapi;
+mod smapi_log;
+mod state;
+mod store;
 const PROJECT_NAME: &str = "SDMM";
 fn main() {
     #[allow(unused_assignments)]
     let mut path = PathBuf::new();
+    if let Err(e) = logging::init(logging::Level::default()) {
+        eprintln!("Failed to open the log file: {e}");
+    }
     let mut args = env::args().skip(1);
     if let Some(pos_url) = args.next() {
         if !pos_url.is_empty() && pos_url.starts_with("nxm://") {
             path = PathBuf::from(pos_url);
             if let Ok(mut stream) = LocalSocketStream::connect("/tmp/sdmm.sock") {
-                println!("{:?}", stream.peer_pid());
+                logging::info!("Sending the link to SDMM {:?}", stream.peer_pid());
                 let path_string = path.display().to_string();
                 let path_bytes = path_string.as_bytes();
                 let mut bytes = vec![0u8; 1024 - path_bytes.len()];
@@ -0,0 +1,279 @@
+use crate::app::GameMod;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+/// Version of the modlist format this build writes
+pub const MODLIST_VERSION: u64 = 1;
+
+/// A shareable list of mods, without anything tied to one computer
+#[derive(Serialize, Deserialize)]
+pub struct ModList {
+    pub version: u64,
+    pub mods: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub mod_id: u64,
+    pub file_id: u64,
+    #[serde(default)]
+    pub unique_ids: Vec<String>,
+    pub version: String,
+    pub source: Source,
+}
+
+/// Where the mod came from, only NexusMods mods can be fetched again
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Nexus,
+    /// Added from a zip on disk
+    Local,
+    /// Installed outside of SDMM
+    External,
+}
+
+impl Entry {
+    /// `local_ids` is where the ids SDMM makes up for mods without NexusMods ids start
+    pub fn new(r#mod: &GameMod, local_ids: u64) -> Entry {
+        let source = if r#mod.external {
+            Source::External
+        } else if r#mod.mod_id == 0 || r#mod.mod_id >= local_ids {
+            Source::Local
+        } else {
+            Source::Nexus
+        };
+        let nexus = source == Source::Nexus;
+        Entry {
+            name: r#mod.name.clone(),
+            mod_id: if nexus { r#mod.mod_id } else { 0 },
+            file_id: if nexus { r#mod.file_id } else { 0 },
+            unique_ids: r#mod.unique_ids.clone(),
+            version: r#mod.version.clone(),
+            source,
+        }
+    }
+
+    /// Whether `r#mod` is the mod and version this entry describes
+    pub fn matches(&self, r#mod: &GameMod) -> bool {
+        if self.source == Source::Nexus && self.mod_id == r#mod.mod_id {
+            // Mods saved before file ids were tracked only know their version
+            return self.file_id == r#mod.file_id
+                || (r#mod.file_id == 0 || self.file_id == 0) && self.version == r#mod.version;
+        }
+        let same_id = self
+            .unique_ids
+            .iter()
+            .any(|id| r#mod.unique_ids.contains(id));
+        (same_id || self.name == r#mod.name) && self.version == r#mod.version
+    }
+
+    /// Whether `r#mod` is the same mod, maybe in another version
+    pub fn same_mod(&self, r#mod: &GameMod) -> bool {
+        if self.source == Source::Nexus {
+            return self.mod_id == r#mod.mod_id;
+        }
+        self.name == r#mod.name
+            || self
+                .unique_ids
+                .iter()
+                .any(|id| r#mod.unique_ids.contains(id))
+    }
+
+    /// The NexusMods page listing this entry's files
+    pub fn page(&self) -> Option<String> {
+        (self.source == Source::Nexus).then(|| {
+            format!(
+                "https://www.nexusmods.com/stardewvalley/mods/{}?tab=files&file_id={}",
+                self.mod_id, self.file_id
+            )
+        })
+    }
+
+    /// Link that premium accounts can download the entry's file with
+    pub fn nxm(&self) -> Option<String> {
+        (self.source == Source::Nexus && self.file_id != 0).then(|| {
+            format!(
+                "nxm://stardewvalley/mods/{}/files/{}",
+                self.mod_id, self.file_id
+            )
+        })
+    }
+}
+
+/// How someone else's mods differ from the active ones
+pub enum Difference {
+    /// Active for them, not for me
+    Missing(Entry),
+    /// Active for me, not for them
+    Extra(GameMod),
+    /// Active on both sides in different versions
+    Version { theirs: Entry, mine: GameMod },
+}
+
+/// Compares another player's list with the active mods, mods from the list that are active in
+/// the same version are left out
+pub fn compare(theirs: &[Entry], active: &[GameMod]) -> Vec<Difference> {
+    let mut differences = vec![];
+    for entry in theirs {
+        if active.iter().any(|m| entry.matches(m)) {
+            continue;
+        }
+        match active.iter().find(|m| entry.same_mod(m)) {
+            Some(mine) => differences.push(Difference::Version {
+                theirs: entry.clone(),
+                mine: mine.clone(),
+            }),
+            None => differences.push(Difference::Missing(entry.clone())),
+        }
+    }
+    for r#mod in active {
+        if !theirs.iter().any(|e| e.matches(r#mod) || e.same_mod(r#mod)) {
+            differences.push(Difference::Extra(r#mod.clone()));
+        }
+    }
+    differences
+}
+
+pub fn save(path: &Path, mods: Vec<Entry>) -> Result<()> {
+    let list = ModList {
+        version: MODLIST_VERSION,
+        mods,
+    };
+    let json =
+        serde_json::to_string_pretty(&list).map_err(|e| Error::InvalidModList(e.to_string()))?;
+    write(path, json)?;
+    Ok(())
+}
+
+/// Reads a mod list, lockfiles work too as their entries carry the same fields
+pub fn load(path: &Path) -> Result<ModList> {
+    let list: ModList = serde_json::from_str(&read_to_string(path)?)
+        .map_err(|e| Error::InvalidModList(e.to_string()))?;
+    if list.version > MODLIST_VERSION {
+        return Err(Error::InvalidModList(format!(
+            "version {} is newer than the supported {MODLIST_VERSION}",
+            list.version
+        )));
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where the app starts handing out ids of its own
+    const LOCAL: u64 = u64::MAX - 10000;
+
+    fn game_mod(name: &str, mod_id: u64, file_id: u64, version: &str, unique_id: &str) -> GameMod {
+        let mut r#mod = GameMod::default();
+        r#mod.name = name.to_string();
+        (r#mod.mod_id, r#mod.file_id) = (mod_id, file_id);
+        r#mod.version = version.to_string();
+        r#mod.unique_ids = vec![unique_id.to_string()];
+        r#mod
+    }
+
+    fn kinds(differences: &[Difference]) -> Vec<String> {
+        differences
+            .iter()
+            .map(|d| match d {
+                Difference::Missing(entry) => format!("missing {}", entry.name),
+                Difference::Extra(r#mod) => format!("extra {}", r#mod.name),
+                Difference::Version { theirs, mine } => {
+                    format!(
+                        "version {} {} {}",
+                        theirs.name, theirs.version, mine.version
+                    )
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sources_follow_the_ids() {
+        let nexus = Entry::new(
+            &game_mod("CP", 1915, 76360, "1.28.0", "Pathoschild.CP"),
+            LOCAL,
+        );
+        assert_eq!(nexus.source, Source::Nexus);
+        assert_eq!((nexus.mod_id, nexus.file_id), (1915, 76360));
+
+        let local = Entry::new(
+            &game_mod("Zip", LOCAL + 3, LOCAL + 3, "1.0", "a.Zip"),
+            LOCAL,
+        );
+        assert_eq!(local.source, Source::Local);
+        assert_eq!((local.mod_id, local.file_id), (0, 0));
+        assert_eq!(local.nxm(), None);
+
+        let mut external = game_mod("Hand", 1915, 1, "1.0", "a.Hand");
+        external.external = true;
+        assert_eq!(Entry::new(&external, LOCAL).source, Source::External);
+    }
+
+    #[test]
+    fn nexus_entries_match_by_ids() {
+        let entry = Entry::new(
+            &game_mod("CP", 1915, 76360, "1.28.0", "Pathoschild.CP"),
+            LOCAL,
+        );
+        // Name and unique id don't matter once the ids are known
+        assert!(entry.matches(&game_mod("Renamed", 1915, 76360, "?", "Other")));
+        assert!(!entry.matches(&game_mod("CP", 1915, 80000, "1.28.0", "Pathoschild.CP")));
+        assert!(entry.same_mod(&game_mod("CP", 1915, 80000, "1.29.0", "Pathoschild.CP")));
+        assert!(!entry.same_mod(&game_mod("CP", 2000, 76360, "1.28.0", "Pathoschild.CP")));
+        // Without a file id the version decides
+        assert!(entry.matches(&game_mod("CP", 1915, 0, "1.28.0", "")));
+        assert!(!entry.matches(&game_mod("CP", 1915, 0, "1.29.0", "")));
+    }
+
+    #[test]
+    fn other_entries_match_by_unique_id_or_name() {
+        let entry = Entry::new(
+            &game_mod("Zip", LOCAL + 1, LOCAL + 1, "1.0", "a.Zip"),
+            LOCAL,
+        );
+        assert!(entry.matches(&game_mod("Renamed", LOCAL + 7, LOCAL + 7, "1.0", "a.Zip")));
+        assert!(entry.matches(&game_mod("Zip", LOCAL + 7, LOCAL + 7, "1.0", "b.Zip")));
+        assert!(!entry.matches(&game_mod("Zip", LOCAL + 1, LOCAL + 1, "2.0", "a.Zip")));
+        assert!(!entry.matches(&game_mod("Other", LOCAL + 1, LOCAL + 1, "1.0", "b.Other")));
+        assert!(entry.same_mod(&game_mod("Renamed", 5, 5, "2.0", "a.Zip")));
+    }
+
+    #[test]
+    fn compare_lists_missing_extra_and_other_versions() {
+        let theirs: Vec<Entry> = [
+            game_mod("CP", 1915, 76360, "1.28.0", "Pathoschild.CP"),
+            game_mod("Lookup", 541, 100, "1.40.0", "Pathoschild.LookupAnything"),
+            game_mod("Zip", LOCAL + 1, LOCAL + 1, "1.0", "a.Zip"),
+            game_mod("Tractor", 1401, 50, "4.16.0", "Pathoschild.TractorMod"),
+        ]
+        .iter()
+        .map(|m| Entry::new(m, LOCAL))
+        .collect();
+        let active = [
+            game_mod("CP", 1915, 76360, "1.28.0", "Pathoschild.CP"),
+            game_mod("Lookup", 541, 120, "1.41.0", "Pathoschild.LookupAnything"),
+            game_mod("Zip", LOCAL + 9, LOCAL + 9, "1.0", "a.Zip"),
+            game_mod("Automate", 1063, 70, "1.27.0", "Pathoschild.Automate"),
+        ];
+        assert_eq!(
+            kinds(&compare(&theirs, &active)),
+            vec![
+                "version Lookup 1.40.0 1.41.0",
+                "missing Tractor",
+                "extra Automate"
+            ]
+        );
+        let same: Vec<GameMod> = theirs
+            .iter()
+            .map(|e| game_mod(&e.name, e.mod_id, e.file_id, &e.version, &e.unique_ids[0]))
+            .collect();
+        assert!(compare(&theirs, &same).is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::logging::{error, info, warning};
+use eframe::egui;
+use std::fmt::Display;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds info and warning toasts stay on screen, errors stay until closed
+const TOAST_SECONDS: f64 = 5.;
+/// Oldest notifications are dropped from the history past this many
+const HISTORY_LIMIT: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn color(self, visuals: &egui::Visuals) -> egui::Color32 {
+        match self {
+            Level::Info => visuals.text_color(),
+            Level::Warning => egui::Color32::GOLD,
+            Level::Error => egui::Color32::RED,
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Level::Info => "ℹ",
+            Level::Warning => "⚠",
+            Level::Error => "❌",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub level: Level,
+    pub message: String,
+    /// Unix time it was sent at
+    pub time: u64,
+}
+
+/// Sends notifications to the app from any thread
+#[derive(Clone)]
+pub struct Notifier {
+    sender: Sender<Notification>,
+    ctx: egui::Context,
+}
+
+impl Notifier {
+    pub fn info(&self, message: impl Display) {
+        self.send(Level::Info, message);
+    }
+
+    pub fn warning(&self, message: impl Display) {
+        self.send(Level::Warning, message);
+    }
+
+    pub fn error(&self, message: impl Display) {
+        self.send(Level::Error, message);
+    }
+
+    pub fn send(&self, level: Level, message: impl Display) {
+        let message = message.to_string();
+        match level {
+            Level::Info => info!("{message}"),
+            Level::Warning => warning!("{message}"),
+            Level::Error => error!("{message}"),
+        }
+        let _ = self.sender.send(Notification {
+            level,
+            message,
+            time: unix_time(),
+        });
+        self.ctx.request_repaint();
+    }
+}
+
+/// The toasts on screen and every notification received so far
+pub struct Notifications {
+    receiver: Receiver<Notification>,
+    /// Index into `history` and the time the toast was first drawn at
+    toasts: Vec<(usize, Option<f64>)>,
+    pub history: Vec<Notification>,
+    /// Notifications received since the history was last looked at
+    pub unread: usize,
+}
+
+impl Notifications {
+    pub fn new(ctx: egui::Context) -> (Self, Notifier) {
+        let (sender, receiver) = channel();
+        let notifications = Self {
+            receiver,
+            toasts: vec![],
+            history: vec![],
+            unread: 0,
+        };
+        (notifications, Notifier { sender, ctx })
+    }
+
+    /// Moves newly sent notifications into the history and onto the screen
+    pub fn poll(&mut self) {
+        while let Ok(notification) = self.receiver.try_recv() {
+            if self.history.len() == HISTORY_LIMIT {
+                self.history.remove(0);
+                self.toasts.retain(|(i, _)| *i > 0);
+                self.toasts.iter_mut().for_each(|(i, _)| *i -= 1);
+            }
+            self.history.push(notification);
+            self.toasts.push((self.history.len() - 1, None));
+            self.unread += 1;
+        }
+    }
+
+    /// Draws the toasts in the bottom right corner, newest at the bottom
+    pub fn toasts_display(&mut self, ctx: &egui::Context) {
+        if self.toasts.is_empty() {
+            return;
+        }
+        let now = ctx.input().time;
+        let history = &self.history;
+        self.toasts.retain(|(i, shown)| {
+            history[*i].level == Level::Error
+                || shown.map_or(true, |shown| now - shown < TOAST_SECONDS)
+        });
+        let mut closed = None;
+        egui::Area::new("toasts")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10., -10.))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                for (toast, (i, shown)) in self.toasts.iter_mut().enumerate() {
+                    shown.get_or_insert(now);
+                    let notification = &history[*i];
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.set_max_width(360.);
+                        ui.horizontal(|ui| {
+                            let color = notification.level.color(ui.visuals());
+                            ui.colored_label(color, notification.level.icon());
+                            ui.label(&notification.message);
+                            if ui.small_button("✖").clicked() {
+                                closed = Some(toast);
+                            }
+                        });
+                    });
+                }
+            });
+        if let Some(toast) = closed {
+            self.toasts.remove(toast);
+        }
+        if self
+            .toasts
+            .iter()
+            .any(|(i, _)| history[*i].level != Level::Error)
+        {
+            // Keep repainting so the toasts disappear on time
+            ctx.request_repaint();
+        }
+    }
+
+    /// Lists every notification, newest first
+    pub fn history_display(&mut self, ui: &mut egui::Ui) {
+        self.unread = 0;
+        ui.horizontal(|ui| {
+            ui.heading("Notifications");
+            if ui
+                .add_enabled(!self.history.is_empty(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.history.clear();
+                self.toasts.clear();
+            }
+        });
+        ui.separator();
+        if self.history.is_empty() {
+            ui.weak("Nothing yet");
+            return;
+        }
+        let now = unix_time();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for notification in self.history.iter().rev() {
+                ui.horizontal_wrapped(|ui| {
+                    let color = notification.level.color(ui.visuals());
+                    ui.colored_label(color, notification.level.icon());
+                    ui.weak(ago(now.saturating_sub(notification.time)));
+                    ui.label(&notification.message);
+                });
+            }
+        });
+    }
+}
+
+/// A question the user has to answer before anything else, with what to do on yes
+pub struct Confirmation<A> {
+    pub title: String,
+    pub message: String,
+    pub confirm: String,
+    pub action: A,
+}
+
+impl<A> Confirmation<A> {
+    /// Draws the question over a dimmed window, `Some(true)` once confirmed and `Some(false)`
+    /// once cancelled
+    pub fn show(&self, ctx: &egui::Context) -> Option<bool> {
+        let screen = ctx.input().screen_rect();
+        // Swallows clicks meant for everything behind the question
+        egui::Area::new("confirmation-backdrop")
+            .fixed_pos(screen.min)
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                ui.painter()
+                    .rect_filled(screen, 0., egui::Color32::from_black_alpha(120));
+                ui.allocate_rect(screen, egui::Sense::click_and_drag());
+            });
+        let mut answer = None;
+        let response = egui::Area::new("confirmation")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::window(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(400.);
+                    ui.heading(&self.title);
+                    ui.label(&self.message);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button(&self.confirm).clicked() {
+                            answer = Some(true);
+                        }
+                        if ui.button("Cancel").clicked()
+                            || ui.input().key_pressed(egui::Key::Escape)
+                        {
+                            answer = Some(false);
+                        }
+                    });
+                });
+            });
+        ctx.move_to_top(response.response.layer_id);
+        answer
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Rough age of a notification, like `5m ago`
+fn ago(seconds: u64) -> String {
+    match seconds {
+        0..=59 => String::from("just now"),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
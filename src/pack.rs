@@ -0,0 +1,141 @@
+use crate::app::GameMod;
+use crate::error::{Context, Error, Result};
+use crate::receipt::hash_file;
+use crate::store::{is_hash, ArchiveEntry, ArchiveStore};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, remove_file, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// File extension of packs, they are zips underneath
+pub const PACK_EXTENSION: &str = "sdmmpack";
+/// Version of the pack format this build writes
+pub const PACK_VERSION: u64 = 1;
+const MANIFEST: &str = "pack.json";
+
+/// An installation's mods with every archive they need, to set them up without internet
+#[derive(Serialize, Deserialize)]
+pub struct Pack {
+    pub version: u64,
+    /// Name of the installation the pack was made from
+    pub name: String,
+    pub active: Vec<GameMod>,
+    pub inactive: Vec<GameMod>,
+    /// Store entry of every archive in the pack, by hash
+    pub archives: HashMap<String, ArchiveEntry>,
+}
+
+fn archive_name(hash: &str) -> String {
+    format!("archives/{hash}.zip")
+}
+
+/// Writes the mods and their archives into one file, mods without an archive in the store are
+/// left out
+pub fn export(
+    path: &Path,
+    name: String,
+    active: Vec<GameMod>,
+    inactive: Vec<GameMod>,
+    store: &ArchiveStore,
+) -> Result<()> {
+    let in_store = |m: &GameMod| store.entry(&m.hash).is_some();
+    let active: Vec<GameMod> = active.into_iter().filter(in_store).collect();
+    let inactive: Vec<GameMod> = inactive.into_iter().filter(in_store).collect();
+    let archives: HashMap<String, ArchiveEntry> = active
+        .iter()
+        .chain(inactive.iter())
+        .filter_map(|m| Some((m.hash.clone(), store.entry(&m.hash)?.clone())))
+        .collect();
+    let pack = Pack {
+        version: PACK_VERSION,
+        name,
+        active,
+        inactive,
+        archives,
+    };
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let json =
+        serde_json::to_string_pretty(&pack).map_err(|e| Error::InvalidPack(e.to_string()))?;
+    zip.start_file(MANIFEST, FileOptions::default())?;
+    zip.write_all(json.as_bytes())?;
+    // The archives are compressed already
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+    for hash in pack.archives.keys() {
+        let source = store.path(hash);
+        let mut file =
+            File::open(&source).context(|| format!("Failed to read {}", source.display()))?;
+        zip.start_file(archive_name(hash), stored)?;
+        io::copy(&mut file, &mut zip)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// A pack whose archives were checked and unpacked, but not yet moved into the store
+pub struct Extracted {
+    pub pack: Pack,
+    /// Where each archive missing from the store was unpacked to, by hash
+    pub archives: Vec<(String, PathBuf)>,
+}
+
+/// Reads a pack and unpacks the archives that are not `known` into `partial`, checking each
+/// against its hash. Meant for the download runtime, the archives can be large.
+pub fn extract(path: &Path, partial: &Path, known: &HashSet<String>) -> Result<Extracted> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let mut json = String::new();
+    zip.by_name(MANIFEST)?.read_to_string(&mut json)?;
+    let pack: Pack = serde_json::from_str(&json).map_err(|e| Error::InvalidPack(e.to_string()))?;
+    if pack.version > PACK_VERSION {
+        return Err(Error::InvalidPack(format!(
+            "version {} is newer than the supported {PACK_VERSION}",
+            pack.version
+        )));
+    }
+    // Hashes end up in paths, anything else could point outside the store
+    let hashes = pack.archives.keys().map(String::as_str).chain(
+        pack.active
+            .iter()
+            .chain(pack.inactive.iter())
+            .map(|m| m.hash.as_str())
+            .filter(|hash| !hash.is_empty()),
+    );
+    for hash in hashes {
+        if !is_hash(hash) {
+            return Err(Error::InvalidPack(format!(
+                "{hash:?} is not an archive hash"
+            )));
+        }
+    }
+    create_dir_all(partial)?;
+    let mut archives = vec![];
+    for hash in pack.archives.keys() {
+        if known.contains(hash) {
+            continue;
+        }
+        let temp = partial.join(format!("{hash}.{PACK_EXTENSION}"));
+        let mut file = File::create(&temp)?;
+        let copied = io::copy(&mut zip.by_name(&archive_name(hash))?, &mut file);
+        drop(file);
+        let checked = copied.and_then(|_| hash_file(&temp));
+        let result = match checked {
+            Ok(actual) if actual == *hash => Ok(()),
+            Ok(_) => Err(Error::InvalidPack(format!("archive {hash} is damaged"))),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            let _ = remove_file(&temp);
+            for (_, temp) in &archives {
+                let _ = remove_file(temp);
+            }
+            return Err(e);
+        }
+        archives.push((hash.clone(), temp));
+    }
+    Ok(Extracted { pack, archives })
+}
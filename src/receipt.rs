@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{read_dir, remove_dir, remove_dir_all, remove_file, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A single file written by an install, relative to the directory the mod was installed into
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InstalledFile {
+    pub path: PathBuf,
+    pub hash: String,
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Builds the receipt for files that were just written under `base`
+pub fn create(base: &Path, files: &[PathBuf]) -> io::Result<Vec<InstalledFile>> {
+    files
+        .iter()
+        .map(|path| {
+            Ok(InstalledFile {
+                path: path.clone(),
+                hash: hash_file(&base.join(path))?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the files from the receipt that were changed since they were installed.
+///
+/// A file that exists but can't be read counts as changed, so it is never removed unchecked.
+pub fn modified_files(base: &Path, receipt: &[InstalledFile]) -> Vec<PathBuf> {
+    receipt
+        .iter()
+        .filter(|file| match hash_file(&base.join(&file.path)) {
+            Ok(hash) => hash != file.hash,
+            Err(e) => e.kind() != io::ErrorKind::NotFound,
+        })
+        .map(|file| file.path.clone())
+        .collect()
+}
+
+/// Removes everything listed in the receipt, then the mod's folders.
+///
+/// Files in `shared` belong to another installed mod and are left in place, as is any folder
+/// still holding one of them. Other folders are removed entirely so files the mod generated
+/// itself, like its `config.json`, go with it.
+pub fn uninstall(
+    base: &Path,
+    receipt: &[InstalledFile],
+    folders: &[String],
+    shared: &HashSet<PathBuf>,
+) -> io::Result<()> {
+    for file in receipt {
+        if shared.contains(&file.path) {
+            continue;
+        }
+        if let Err(e) = remove_file(base.join(&file.path))
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            return Err(e);
+        }
+    }
+    for folder in folders {
+        let path = base.join(folder);
+        if shared.iter().any(|file| file.starts_with(folder)) {
+            remove_empty_dirs(&path)?;
+        } else if let Err(e) = remove_dir_all(&path)
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Removes only the files listed in the receipt, then each folder that was left empty by it,
+/// up to `base`
+pub fn remove_files(base: &Path, receipt: &[InstalledFile]) -> io::Result<()> {
+    for file in receipt {
+        if let Err(e) = remove_file(base.join(&file.path))
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            return Err(e);
+        }
+    }
+    for file in receipt {
+        for folder in file.path.ancestors().skip(1) {
+            // Fails for folders that still hold something, and their parents do too
+            if folder.as_os_str().is_empty() || remove_dir(base.join(folder)).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the paths that appear in both receipts
+pub fn conflicts(planned: &[PathBuf], receipt: &[InstalledFile]) -> Vec<PathBuf> {
+    receipt
+        .iter()
+        .filter(|file| planned.contains(&file.path))
+        .map(|file| file.path.clone())
+        .collect()
+}
+
+fn remove_empty_dirs(path: &Path) -> io::Result<bool> {
+    let mut empty = true;
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            empty &= remove_empty_dirs(&entry.path())?;
+        } else {
+            empty = false;
+        }
+    }
+    if empty {
+        remove_dir(path)?;
+    }
+    Ok(empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    /// Writes `files` under `base` with their own path as content and returns their receipt
+    fn install(base: &Path, files: &[&str]) -> Vec<InstalledFile> {
+        let files: Vec<PathBuf> = files.iter().map(PathBuf::from).collect();
+        for file in &files {
+            create_dir_all(base.join(file).parent().unwrap()).unwrap();
+            write(base.join(file), file.to_string_lossy().as_bytes()).unwrap();
+        }
+        create(base, &files).unwrap()
+    }
+
+    #[test]
+    fn unchanged_files_are_not_modified() {
+        let temp = tempfile::tempdir().unwrap();
+        let receipt = install(temp.path(), &["Mod/manifest.json", "Mod/Mod.dll"]);
+        assert!(modified_files(temp.path(), &receipt).is_empty());
+    }
+
+    #[test]
+    fn changed_and_unreadable_files_are_modified() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let receipt = install(base, &["Mod/manifest.json", "Mod/Mod.dll", "Mod/a.png"]);
+        write(base.join("Mod/manifest.json"), "{}").unwrap();
+        remove_file(base.join("Mod/a.png")).unwrap();
+        create_dir_all(base.join("Mod/a.png")).unwrap();
+        remove_file(base.join("Mod/Mod.dll")).unwrap();
+        assert_eq!(
+            modified_files(base, &receipt),
+            vec![
+                PathBuf::from("Mod/manifest.json"),
+                PathBuf::from("Mod/a.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn uninstall_removes_files_and_folders() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let receipt = install(base, &["Mod/manifest.json", "Mod/assets/a.png"]);
+        write(base.join("Mod/config.json"), "{}").unwrap();
+        write(base.join("Other.txt"), "").unwrap();
+        uninstall(base, &receipt, &["Mod".into()], &HashSet::new()).unwrap();
+        assert!(!base.join("Mod").exists());
+        assert!(base.join("Other.txt").exists());
+    }
+
+    #[test]
+    fn uninstall_keeps_shared_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let receipt = install(base, &["Mod/manifest.json", "Mod/assets/shared.png"]);
+        let shared = HashSet::from([PathBuf::from("Mod/assets/shared.png")]);
+        uninstall(base, &receipt, &["Mod".into()], &shared).unwrap();
+        assert!(!base.join("Mod/manifest.json").exists());
+        assert!(base.join("Mod/assets/shared.png").exists());
+    }
+
+    #[test]
+    fn remove_files_keeps_other_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let receipt = install(
+            base,
+            &[
+                "StardewModdingAPI.dll",
+                "smapi-internal/config.json",
+                "Mods/ConsoleCommands/manifest.json",
+                "Mods/SaveBackup/manifest.json",
+            ],
+        );
+        write(base.join("Mods/SaveBackup/backup.zip"), "").unwrap();
+        write(base.join("Stardew Valley.dll"), "").unwrap();
+        remove_files(base, &receipt).unwrap();
+        assert!(!base.join("StardewModdingAPI.dll").exists());
+        assert!(!base.join("smapi-internal").exists());
+        assert!(!base.join("Mods/ConsoleCommands").exists());
+        assert!(base.join("Mods/SaveBackup/backup.zip").exists());
+        assert!(!base.join("Mods/SaveBackup/manifest.json").exists());
+        assert!(base.join("Stardew Valley.dll").exists());
+    }
+
+    #[test]
+    fn conflicts_are_paths_in_both() {
+        let temp = tempfile::tempdir().unwrap();
+        let receipt = install(temp.path(), &["Mod/manifest.json", "Mod/a.png"]);
+        let planned = vec![PathBuf::from("Mod/a.png"), PathBuf::from("Mod/b.png")];
+        assert_eq!(
+            conflicts(&planned, &receipt),
+            vec![PathBuf::from("Mod/a.png")]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::error::{Error, Result};
+use crate::receipt::{self, InstalledFile};
+use std::fs::{copy, create_dir_all, rename, File};
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// NexusMods id of SMAPI itself
+pub const SMAPI_MOD_ID: u64 = 2400;
+
+#[cfg(target_os = "windows")]
+pub const PLATFORM: &str = "windows";
+#[cfg(target_os = "macos")]
+pub const PLATFORM: &str = "macOS";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub const PLATFORM: &str = "linux";
+
+const GAME_DEPS: &str = "Stardew Valley.deps.json";
+const SMAPI_DEPS: &str = "StardewModdingAPI.deps.json";
+const UNIX_LAUNCHER: &str = "unix-launcher.sh";
+const SMAPI_LAUNCHER: &str = "StardewModdingAPI";
+
+/// Installs SMAPI from its installer archive into the game folder and returns everything that
+/// was written, relative to `game_path`
+pub fn install(installer: &File, game_path: &Path) -> Result<Vec<InstalledFile>> {
+    let game_deps = game_path.join(GAME_DEPS);
+    if !game_deps.exists() {
+        return Err(Error::MissingGameFile(game_deps));
+    }
+    let mut install_data = read_install_data(installer)?;
+    let mut files: Vec<PathBuf> = vec![];
+    for i in 0..install_data.len() {
+        let mut entry = install_data.by_index(i)?;
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        let outpath = game_path.join(&relative);
+        if entry.is_dir() {
+            create_dir_all(&outpath)?;
+            continue;
+        }
+        if let Some(parent) = outpath.parent() {
+            create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&outpath)?)?;
+        files.push(relative);
+    }
+
+    if let Some(position) = files.iter().position(|f| f == Path::new(UNIX_LAUNCHER)) {
+        rename(
+            game_path.join(UNIX_LAUNCHER),
+            game_path.join(SMAPI_LAUNCHER),
+        )?;
+        files[position] = PathBuf::from(SMAPI_LAUNCHER);
+        #[cfg(unix)]
+        {
+            use std::fs::{set_permissions, Permissions};
+            use std::os::unix::fs::PermissionsExt;
+            set_permissions(
+                game_path.join(SMAPI_LAUNCHER),
+                Permissions::from_mode(0o755),
+            )?;
+        }
+    }
+    copy(&game_deps, game_path.join(SMAPI_DEPS))?;
+    files.push(PathBuf::from(SMAPI_DEPS));
+
+    Ok(receipt::create(game_path, &files)?)
+}
+
+/// Removes everything a previous [`install`] wrote, including the mods bundled with SMAPI.
+///
+/// Only the files SMAPI wrote are removed, whatever else is in its folders stays along with
+/// the folders holding it.
+pub fn uninstall(game_path: &Path, installed: &[InstalledFile]) -> Result<()> {
+    receipt::remove_files(game_path, installed)?;
+    Ok(())
+}
+
+/// Removes a SMAPI install made before SDMM kept receipts, using the installer archive to work
+/// out which files belong to SMAPI
+pub fn uninstall_legacy(installer: &File, game_path: &Path) -> Result<()> {
+    let mut install_data = read_install_data(installer)?;
+    let mut installed = vec![];
+    for i in 0..install_data.len() {
+        let entry = install_data.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        if let Some(path) = entry.enclosed_name() {
+            let path = if path == Path::new(UNIX_LAUNCHER) {
+                PathBuf::from(SMAPI_LAUNCHER)
+            } else {
+                path.to_path_buf()
+            };
+            installed.push(InstalledFile {
+                path,
+                hash: String::new(),
+            });
+        }
+    }
+    installed.push(InstalledFile {
+        path: PathBuf::from(SMAPI_DEPS),
+        hash: String::new(),
+    });
+    uninstall(game_path, &installed)
+}
+
+/// Opens the `install.dat` for this platform from inside the installer archive
+fn read_install_data(installer: &File) -> Result<ZipArchive<Cursor<Vec<u8>>>> {
+    let mut archive = ZipArchive::new(installer)?;
+    let suffix = format!("internal/{PLATFORM}/install.dat");
+    let name = archive
+        .file_names()
+        .find(|name| name.ends_with(&suffix))
+        .ok_or(Error::NotSmapiInstaller)?
+        .to_string();
+    let mut bytes = vec![];
+    archive.by_name(&name)?.read_to_end(&mut bytes)?;
+    Ok(ZipArchive::new(Cursor::new(bytes))?)
+}
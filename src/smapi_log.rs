@@ -0,0 +1,360 @@
+use directories_next::BaseDirs;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Alert,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::Trace,
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Alert,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    fn parse(text: &str) -> Option<LogLevel> {
+        match text {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "ALERT" => Some(LogLevel::Alert),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Alert => "ALERT",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+pub struct LogEntry {
+    pub time: String,
+    pub level: LogLevel,
+    /// The mod that wrote the entry, or SMAPI itself
+    pub source: String,
+    pub message: String,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ProblemKind {
+    /// SMAPI refused to load the mod
+    Skipped,
+    /// The mod was skipped because mods it depends on are not installed
+    MissingDependency,
+    /// A newer version of the mod is available
+    Outdated,
+    /// The mod logged an error or exception while running
+    Error,
+}
+
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Name of the mod as SMAPI shows it
+    pub mod_name: String,
+    pub detail: String,
+    /// Update page for outdated mods
+    pub link: Option<String>,
+    /// UniqueIDs of the missing dependencies
+    pub missing: Vec<String>,
+}
+
+pub struct SmapiLog {
+    pub path: PathBuf,
+    pub entries: Vec<LogEntry>,
+    pub problems: Vec<Problem>,
+}
+
+/// Where SMAPI writes the log of the last session
+pub fn latest_log_path() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| {
+        // .NET keeps application data in ~/.config on macOS too, not in Application Support
+        #[cfg(target_os = "macos")]
+        let config = dirs.home_dir().join(".config");
+        #[cfg(not(target_os = "macos"))]
+        let config = dirs.config_dir().to_path_buf();
+        config
+            .join("StardewValley")
+            .join("ErrorLogs")
+            .join("SMAPI-latest.txt")
+    })
+}
+
+impl SmapiLog {
+    pub fn load(path: &Path) -> io::Result<SmapiLog> {
+        let entries = parse(&read_to_string(path)?);
+        let problems = analyse(&entries);
+        Ok(SmapiLog {
+            path: path.to_path_buf(),
+            entries,
+            problems,
+        })
+    }
+
+    /// Every source that wrote to the log, in order of appearance
+    pub fn sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = vec![];
+        for entry in &self.entries {
+            if !sources.contains(&entry.source.as_str()) {
+                sources.push(&entry.source);
+            }
+        }
+        sources
+    }
+}
+
+/// Splits the log into entries, lines without a `[time LEVEL source]` prefix continue the
+/// previous entry
+pub fn parse(text: &str) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = vec![];
+    for line in text.lines() {
+        match parse_prefix(line) {
+            Some((time, level, source, message)) => entries.push(LogEntry {
+                time: time.to_string(),
+                level,
+                source: source.to_string(),
+                message: message.to_string(),
+            }),
+            None => {
+                if let Some(last) = entries.last_mut() {
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+            }
+        }
+    }
+    entries
+}
+
+fn parse_prefix(line: &str) -> Option<(&str, LogLevel, &str, &str)> {
+    let rest = line.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    let (header, message) = (&rest[..end], &rest[end + 1..]);
+    let mut parts = header.splitn(2, ' ');
+    let time = parts.next()?;
+    let rest = parts.next()?.trim_start();
+    let (level, source) = rest.split_once(' ').unwrap_or((rest, ""));
+    let level = LogLevel::parse(level)?;
+    Some((
+        time,
+        level,
+        source.trim(),
+        message.strip_prefix(' ').unwrap_or(message),
+    ))
+}
+
+fn analyse(entries: &[LogEntry]) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = vec![];
+    let mut in_updates = false;
+    for entry in entries {
+        if entry.source == "SMAPI" {
+            let message = entry.message.trim();
+            if message.starts_with("You can update") {
+                in_updates = true;
+                continue;
+            }
+            if in_updates {
+                // Update alerts look like `Mod Name 1.2.3: https://... (you have 1.2.0)`
+                if let Some((name, link)) = message.split_once(": http") {
+                    let link = link.split_whitespace().next().unwrap_or_default();
+                    problems.push(Problem {
+                        kind: ProblemKind::Outdated,
+                        mod_name: strip_version(name),
+                        detail: format!("{} is available", name.rsplit(' ').next().unwrap_or(name)),
+                        link: Some(format!("http{link}")),
+                        missing: vec![],
+                    });
+                    continue;
+                }
+                in_updates = false;
+            }
+            // Skipped mods are listed as `- Mod Name 1.0.0 because reason`
+            if entry.level == LogLevel::Error
+                && let Some(line) = message.strip_prefix("- ")
+                && let Some((name, reason)) = line.split_once(" because ")
+            {
+                let missing = if reason.contains("requires mods which aren't installed") {
+                    reason
+                        .split_once('(')
+                        .and_then(|(_, ids)| ids.split_once(')'))
+                        .map(|(ids, _)| ids.split(',').map(|id| id.trim().to_string()).collect())
+                        .unwrap_or_default()
+                } else {
+                    vec![]
+                };
+                problems.push(Problem {
+                    kind: if missing.is_empty() {
+                        ProblemKind::Skipped
+                    } else {
+                        ProblemKind::MissingDependency
+                    },
+                    mod_name: strip_version(name),
+                    detail: reason.trim_end_matches('.').to_string(),
+                    link: None,
+                    missing,
+                });
+            }
+        } else if entry.level == LogLevel::Error {
+            let first_line = entry.message.lines().next().unwrap_or_default().to_string();
+            match problems
+                .iter_mut()
+                .find(|p| p.kind == ProblemKind::Error && p.mod_name == entry.source)
+            {
+                Some(problem) => {
+                    if !problem.detail.contains(&first_line) {
+                        problem.detail.push('\n');
+                        problem.detail.push_str(&first_line);
+                    }
+                }
+                None => problems.push(Problem {
+                    kind: ProblemKind::Error,
+                    mod_name: entry.source.clone(),
+                    detail: first_line,
+                    link: None,
+                    missing: vec![],
+                }),
+            }
+        }
+    }
+    problems
+}
+
+/// Drops the trailing version SMAPI appends to mod names
+fn strip_version(name: &str) -> String {
+    match name.rsplit_once(' ') {
+        Some((rest, version)) if version.chars().next().map_or(false, |c| c.is_ascii_digit()) => {
+            rest.to_string()
+        }
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[12:01:02 INFO  SMAPI] SMAPI 3.18.2 with Stardew Valley 1.5.6 build 22018 on Unix 5.15.0.0
+[12:01:02 INFO  SMAPI] Mods go here: /home/user/.steam/steam/steamapps/common/Stardew Valley/Mods
+[12:01:04 INFO  SMAPI] Loading mods...
+[12:01:05 ERROR SMAPI]    Skipped mods
+[12:01:05 ERROR SMAPI]    --------------------------------------------------
+[12:01:05 ERROR SMAPI]       These mods could not be added to your game.
+[12:01:05 ERROR SMAPI]
+[12:01:05 ERROR SMAPI]       - Json Assets 1.10.6 because it requires mods which aren't installed (spacechase0.SpaceCore, Pathoschild.ContentPatcher).
+[12:01:05 ERROR SMAPI]       - Old Farm Mod 1.0 because it's no longer compatible. Please check for a newer version at https://smapi.io/mods
+[12:01:05 ERROR SMAPI]
+[12:01:06 INFO  SMAPI] Loaded 2 mods:
+[12:01:06 INFO  SMAPI]    Content Patcher 1.28.0 by Pathoschild | Loads content packs.
+[12:01:07 ALERT SMAPI] You can update 2 mods:
+[12:01:07 ALERT SMAPI]    Content Patcher 1.28.4: https://www.nexusmods.com/stardewvalley/mods/1915 (you have 1.28.0)
+[12:01:07 ALERT SMAPI]    Farm Type Manager 1.16.2: https://www.nexusmods.com/stardewvalley/mods/3231 (you have 1.16.0)
+[12:01:07 INFO  SMAPI] Type 'help' for help, or 'help <cmd>' for a command's usage
+[12:02:11 ERROR Farm Type Manager] An error occurred while spawning forage.
+System.NullReferenceException: Object reference not set to an instance of an object.
+   at FarmTypeManager.ModEntry.Utility.SpawnForage(String area)
+[12:02:12 ERROR Farm Type Manager] An error occurred while spawning forage.
+[12:02:13 ERROR Farm Type Manager] Failed to load the farm config.
+[12:02:14 WARN  Content Patcher] Ignored content pack: the manifest has no ContentPackFor.
+";
+
+    #[test]
+    fn splits_entries_and_keeps_continuation_lines() {
+        let entries = parse(LOG);
+        assert_eq!(entries.len(), 20);
+        let first = &entries[0];
+        assert_eq!(first.time, "12:01:02");
+        assert_eq!(first.level, LogLevel::Info);
+        assert_eq!(first.source, "SMAPI");
+        assert!(first.message.starts_with("SMAPI 3.18.2"));
+        let error = &entries[16];
+        assert_eq!(error.source, "Farm Type Manager");
+        assert_eq!(error.message.lines().count(), 3);
+        assert!(error.message.ends_with("SpawnForage(String area)"));
+        assert_eq!(entries[19].level, LogLevel::Warn);
+    }
+
+    #[test]
+    fn ignores_lines_before_the_first_entry() {
+        let entries = parse("Some header\n[00:00:00 TRACE SMAPI] Started");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "Started");
+    }
+
+    #[test]
+    fn finds_skipped_mods_and_missing_dependencies() {
+        let problems = analyse(&parse(LOG));
+        let missing = &problems[0];
+        assert_eq!(missing.kind, ProblemKind::MissingDependency);
+        assert_eq!(missing.mod_name, "Json Assets");
+        assert_eq!(
+            missing.missing,
+            vec!["spacechase0.SpaceCore", "Pathoschild.ContentPatcher"]
+        );
+        let skipped = &problems[1];
+        assert_eq!(skipped.kind, ProblemKind::Skipped);
+        assert_eq!(skipped.mod_name, "Old Farm Mod");
+        assert!(skipped.detail.starts_with("it's no longer compatible"));
+        assert!(skipped.missing.is_empty());
+    }
+
+    #[test]
+    fn finds_outdated_mods() {
+        let problems = analyse(&parse(LOG));
+        let outdated: Vec<&Problem> = problems
+            .iter()
+            .filter(|p| p.kind == ProblemKind::Outdated)
+            .collect();
+        assert_eq!(outdated.len(), 2);
+        assert_eq!(outdated[0].mod_name, "Content Patcher");
+        assert_eq!(outdated[0].detail, "1.28.4 is available");
+        assert_eq!(
+            outdated[0].link.as_deref(),
+            Some("https://www.nexusmods.com/stardewvalley/mods/1915")
+        );
+        assert_eq!(outdated[1].mod_name, "Farm Type Manager");
+    }
+
+    #[test]
+    fn groups_errors_by_mod() {
+        let problems = analyse(&parse(LOG));
+        let errors: Vec<&Problem> = problems
+            .iter()
+            .filter(|p| p.kind == ProblemKind::Error)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].mod_name, "Farm Type Manager");
+        assert_eq!(
+            errors[0].detail,
+            "An error occurred while spawning forage.\nFailed to load the farm config."
+        );
+    }
+
+    #[test]
+    fn strips_trailing_versions() {
+        assert_eq!(strip_version("Content Patcher 1.28.4"), "Content Patcher");
+        assert_eq!(
+            strip_version("Stardew Valley Expanded"),
+            "Stardew Valley Expanded"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use crate::app::Installation;
+use crate::disable::DisableStrategy;
+use crate::download::UpdateCheck;
+use crate::error::{Error, Result};
+use crate::launch::LaunchMethod;
+use crate::logging::{self, error, warning};
+use directories_next::ProjectDirs;
+use eframe::Storage;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::{copy, create_dir_all, read_to_string, rename, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the state file this build writes
+pub const STATE_VERSION: u64 = 2;
+const STATE_FILE: &str = "state.json";
+const BACKUP_FILE: &str = "state.json.bak";
+
+/// Keys SDMM kept in eframe's storage, which make up a version 1 state
+const V1_KEYS: [&str; 6] = [
+    "active_mods",
+    "inactive_mods",
+    "game_path",
+    "download_path",
+    "api_key",
+    "last_download",
+];
+
+/// Schema changes in order, `MIGRATIONS[n]` turns a version `n + 1` file into version `n + 2`
+const MIGRATIONS: [fn(&mut Map<String, Value>); 1] = [group_settings];
+
+/// Everything SDMM remembers between runs
+#[derive(Serialize, Deserialize, Default)]
+pub struct State {
+    pub version: u64,
+    pub settings: Settings,
+    /// Name of the selected installation
+    pub current: String,
+    pub installations: Vec<Installation>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub api_key: String,
+    /// Where the archive store lives, the data directory when unset
+    pub download_path: Option<PathBuf>,
+    /// nxm link that arrived before an API key was set
+    pub last_download: String,
+    pub disable_strategy: DisableStrategy,
+    pub launch_method: LaunchMethod,
+    pub launch_args: String,
+    pub dark_mode: bool,
+    pub update_check: UpdateCheck,
+    pub last_update_check: u64,
+    pub download_concurrency: usize,
+    pub log_level: logging::Level,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            api_key: Default::default(),
+            download_path: Default::default(),
+            last_download: Default::default(),
+            disable_strategy: Default::default(),
+            launch_method: Default::default(),
+            launch_args: Default::default(),
+            dark_mode: true,
+            update_check: Default::default(),
+            last_update_check: Default::default(),
+            download_concurrency: 2,
+            log_level: Default::default(),
+        }
+    }
+}
+
+/// The folder holding the state file
+pub fn state_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", crate::PROJECT_NAME).map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Reads the state file, falling back to the backup if it is unreadable.
+///
+/// Returns `None` when neither exists yet.
+pub fn load() -> Result<Option<State>> {
+    match state_dir() {
+        Some(dir) => load_from(&dir),
+        None => Ok(None),
+    }
+}
+
+fn load_from(dir: &Path) -> Result<Option<State>> {
+    match read_state(&dir.join(STATE_FILE)) {
+        Ok(Some(state)) => Ok(Some(state)),
+        Err(Error::StateTooNew(version)) => Err(Error::StateTooNew(version)),
+        first => {
+            if let Err(e) = &first {
+                warning!("Failed to read {STATE_FILE}, trying {BACKUP_FILE}: {e}");
+                // Keep the broken file out of the way, the next save would make it the backup
+                let _ = rename(
+                    dir.join(STATE_FILE),
+                    dir.join(format!("{STATE_FILE}.broken")),
+                );
+            }
+            match read_state(&dir.join(BACKUP_FILE)) {
+                Ok(None) => first,
+                backup => backup,
+            }
+        }
+    }
+}
+
+/// Writes the state atomically, keeping the previous file as a backup
+pub fn save(state: &State) -> Result<()> {
+    let dir = state_dir().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    save_to(&dir, state)
+}
+
+fn save_to(dir: &Path, state: &State) -> Result<()> {
+    create_dir_all(dir)?;
+    let json = serde_json::to_string_pretty(state).map_err(Error::State)?;
+    let temp = dir.join(format!("{STATE_FILE}.tmp"));
+    let mut file = File::create(&temp)?;
+    file.write_all(json.as_bytes())?;
+    file.sync_all()?;
+    let path = dir.join(STATE_FILE);
+    if path.exists() {
+        copy(&path, dir.join(BACKUP_FILE))?;
+    }
+    // Replaces the old file in one step, so there is always a state.json to read
+    rename(temp, path)?;
+    Ok(())
+}
+
+/// Collects the values SDMM kept in eframe's storage before it had its own state file, as a
+/// version 1 state
+pub fn import_eframe(storage: &dyn Storage) -> Option<State> {
+    let mut map = Map::new();
+    for key in V1_KEYS {
+        // RON structs can't be read straight into JSON, they go through ron's own value type
+        let value = storage
+            .get_string(key)
+            .and_then(|text| ron::from_str::<ron::Value>(&text).ok())
+            .and_then(|v| serde_json::to_value(v).ok());
+        if let Some(value) = value {
+            map.insert(key.to_string(), value);
+        }
+    }
+    if map.is_empty() {
+        return None;
+    }
+    map.insert("version".into(), json!(1));
+    match migrate(map) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            error!("Failed to import the previous settings: {e}");
+            None
+        }
+    }
+}
+
+fn read_state(path: &Path) -> Result<Option<State>> {
+    let text = match read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let map: Map<String, Value> = serde_json::from_str(&text).map_err(Error::State)?;
+    let version = map.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version < STATE_VERSION {
+        // Keep what the older SDMM wrote, in case going back to it is needed
+        let old = path.with_file_name(format!("state.v{version}.json"));
+        if !old.exists() {
+            copy(path, old)?;
+        }
+    }
+    migrate(map).map(Some)
+}
+
+fn migrate(mut map: Map<String, Value>) -> Result<State> {
+    let version = map.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > STATE_VERSION {
+        return Err(Error::StateTooNew(version));
+    }
+    for migration in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+        migration(&mut map);
+    }
+    map.insert("version".into(), json!(STATE_VERSION));
+    serde_json::from_value(Value::Object(map)).map_err(Error::State)
+}
+
+/// Version 1 is the flat key layout of eframe's storage, version 2 groups the settings and
+/// keeps the mods in a list of installations
+fn group_settings(map: &mut Map<String, Value>) {
+    let mut settings = Map::new();
+    for key in ["api_key", "download_path", "last_download"] {
+        if let Some(value) = map.remove(key) {
+            settings.insert(key.to_string(), value);
+        }
+    }
+    let mut installation = json!({
+        "name": "Default",
+        "game_path": map.remove("game_path").unwrap_or_else(|| json!("")),
+        "active": map.remove("active_mods").unwrap_or_else(|| json!([])),
+        "inactive": map.remove("inactive_mods").unwrap_or_else(|| json!([])),
+    });
+    for list in ["active", "inactive"] {
+        if let Some(Value::Array(mods)) = installation.get_mut(list) {
+            mods.iter_mut().for_each(split_legacy_id);
+        }
+    }
+    map.insert("settings".into(), Value::Object(settings));
+    map.insert("current".into(), json!("Default"));
+    map.insert("installations".into(), json!([installation]));
+}
+
+/// Mods saved before file ids were tracked only have an `id`, the file id is looked up later
+fn split_legacy_id(r#mod: &mut Value) {
+    if let Some(object) = r#mod.as_object_mut()
+        && !object.contains_key("mod_id")
+        && let Some(id) = object.remove("id")
+    {
+        object.insert("mod_id".into(), id);
+        object.insert("file_id".into(), json!(0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs::write;
+
+    /// A state in the flat layout of the last release that kept it in eframe's storage
+    fn v1() -> Value {
+        json!({
+            "active_mods": [{
+                "name": "Mod", "zip_name": "Mod.zip", "folder_name": "Mod", "version": "1.0.0",
+                "author": "Someone", "link": "", "mod_id": 1, "file_id": 2
+            }],
+            "inactive_mods": [{
+                "name": "Old mod", "zip_name": "Old.zip", "folder_name": "Old",
+                "version": "0.1.0", "author": "Someone", "link": "", "id": 5
+            }],
+            "game_path": "/games/stardew",
+            "download_path": "/downloads",
+            "api_key": "key",
+            "last_download": "",
+        })
+    }
+
+    fn v2(api_key: &str) -> String {
+        serde_json::to_string(&State {
+            version: STATE_VERSION,
+            settings: Settings {
+                api_key: api_key.into(),
+                ..Default::default()
+            },
+            current: "Main".into(),
+            installations: vec![],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        let Value::Object(map) = v1() else {
+            unreachable!()
+        };
+        let state = migrate(map).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.settings.api_key, "key");
+        assert_eq!(
+            state.settings.download_path,
+            Some(PathBuf::from("/downloads"))
+        );
+        assert!(state.settings.dark_mode);
+        assert_eq!(state.current, "Default");
+        assert_eq!(state.installations.len(), 1);
+        let installation = &state.installations[0];
+        assert_eq!(installation.name, "Default");
+        assert_eq!(installation.game_path, PathBuf::from("/games/stardew"));
+        let active = &installation.active[0];
+        assert_eq!((active.mod_id, active.file_id), (1, 2));
+        let old = &installation.inactive[0];
+        assert_eq!((old.mod_id, old.file_id), (5, 0));
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut map = Map::new();
+        map.insert("version".into(), json!(STATE_VERSION + 1));
+        assert!(matches!(migrate(map), Err(Error::StateTooNew(v)) if v == STATE_VERSION + 1));
+    }
+
+    #[test]
+    fn newer_state_is_not_replaced_by_backup() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(
+            dir.join(STATE_FILE),
+            json!({ "version": STATE_VERSION + 1 }).to_string(),
+        )
+        .unwrap();
+        write(dir.join(BACKUP_FILE), v2("backup")).unwrap();
+        assert!(matches!(load_from(dir), Err(Error::StateTooNew(_))));
+        assert!(dir.join(STATE_FILE).exists());
+    }
+
+    #[test]
+    fn falls_back_to_backup() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join(STATE_FILE), "{ broken").unwrap();
+        write(dir.join(BACKUP_FILE), v2("backup")).unwrap();
+        let state = load_from(dir).unwrap().unwrap();
+        assert_eq!(state.settings.api_key, "backup");
+        assert!(dir.join(format!("{STATE_FILE}.broken")).exists());
+    }
+
+    #[test]
+    fn missing_state_is_none() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        assert!(load_from(dir).unwrap().is_none());
+    }
+
+    #[test]
+    fn save_keeps_previous_as_backup() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        for api_key in ["first", "second"] {
+            let mut state = State {
+                version: STATE_VERSION,
+                ..Default::default()
+            };
+            state.settings.api_key = api_key.into();
+            save_to(dir, &state).unwrap();
+        }
+        assert_eq!(load_from(dir).unwrap().unwrap().settings.api_key, "second");
+        let backup = read_state(&dir.join(BACKUP_FILE)).unwrap().unwrap();
+        assert_eq!(backup.settings.api_key, "first");
+    }
+
+    #[test]
+    fn older_state_is_kept_next_to_migrated_one() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join(STATE_FILE), v1().to_string()).unwrap();
+        let state = load_from(dir).unwrap().unwrap();
+        assert_eq!(state.settings.api_key, "key");
+        assert!(dir.join("state.v1.json").exists());
+    }
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn imports_eframe_storage() {
+        let mut storage = MemoryStorage::default();
+        assert!(import_eframe(&storage).is_none());
+        // As the last release wrote them, the inactive mod predates file ids
+        for (key, value) in [
+            (
+                "active_mods",
+                r#"[(name:"Content Patcher",zip_name:"Content Patcher-1915-1-27-2.zip",folder_name:"ContentPatcher",version:"1.27.2",author:"Pathoschild",link:"https://www.nexusmods.com/stardewvalley/mods/1915",mod_id:1915,file_id:76360)]"#,
+            ),
+            (
+                "inactive_mods",
+                r#"[(name:"Old mod",zip_name:"Old.zip",folder_name:"Old",version:"0.1.0",author:"Unknown",link:"",id:5)]"#,
+            ),
+            ("game_path", r#""/games/Stardew Valley""#),
+            ("download_path", r#""/data/sdmm/mods""#),
+            ("api_key", r#""key""#),
+            ("last_download", r#""""#),
+        ] {
+            storage.set_string(key, value.to_string());
+        }
+
+        let state = import_eframe(&storage).unwrap();
+        assert_eq!(state.settings.api_key, "key");
+        assert_eq!(
+            state.settings.download_path,
+            Some(PathBuf::from("/data/sdmm/mods"))
+        );
+        assert_eq!(state.current, "Default");
+        let installation = &state.installations[0];
+        assert_eq!(
+            installation.game_path,
+            PathBuf::from("/games/Stardew Valley")
+        );
+        let active = &installation.active[0];
+        assert_eq!(active.name, "Content Patcher");
+        assert_eq!((active.mod_id, active.file_id), (1915, 76360));
+        let old = &installation.inactive[0];
+        assert_eq!((old.mod_id, old.file_id), (5, 0));
+    }
+}
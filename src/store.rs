@@ -0,0 +1,542 @@
+use crate::logging::{error, warning};
+use crate::receipt::hash_file;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_file, rename, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = "index.json";
+/// Downloads are written here and only enter the store once they are complete
+pub const PARTIAL_FOLDER: &str = ".partial";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ArchiveSource {
+    pub mod_id: u64,
+    pub file_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct ArchiveEntry {
+    pub sources: Vec<ArchiveSource>,
+    /// Every file name the archive was added under
+    pub names: Vec<String>,
+}
+
+impl ArchiveEntry {
+    fn merge(&mut self, other: &ArchiveEntry) {
+        for name in &other.names {
+            if !self.names.contains(name) {
+                self.names.push(name.clone());
+            }
+        }
+        for source in &other.sources {
+            if !self.sources.contains(source) {
+                self.sources.push(source.clone());
+            }
+        }
+    }
+}
+
+/// Mod archives stored by the hash of their contents, so identical archives are kept once and
+/// archives sharing a file name never overwrite each other
+pub struct ArchiveStore {
+    root: PathBuf,
+    index: HashMap<String, ArchiveEntry>,
+}
+
+/// Whether `name` is a SHA-256 hash as the store names its archives
+pub fn is_hash(name: &str) -> bool {
+    name.len() == 64
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+pub fn partial_path(root: &Path, mod_id: usize, file_id: usize, file_name: &str) -> PathBuf {
+    root.join(PARTIAL_FOLDER)
+        .join(format!("{mod_id}-{file_id}-{file_name}"))
+}
+
+impl ArchiveStore {
+    /// Loads the index in `root`. Stored archives missing from the index are indexed again
+    /// where they are, anything else in the folder is left alone.
+    pub fn open(root: &Path) -> ArchiveStore {
+        ArchiveStore::load(root, false)
+    }
+
+    /// Opens the store in the download folder. The first time, before it has an index, archives
+    /// older versions kept there under their own names are moved into the store.
+    pub fn open_or_import(root: &Path) -> ArchiveStore {
+        ArchiveStore::load(root, !root.join(INDEX_FILE).exists())
+    }
+
+    fn load(root: &Path, import: bool) -> ArchiveStore {
+        let index = match read_to_string(root.join(INDEX_FILE)) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warning!("Archive index is corrupt, rebuilding it: {e}");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        let mut store = ArchiveStore {
+            root: root.to_path_buf(),
+            index,
+        };
+        let mut changed = false;
+        if let Ok(entries) = read_dir(root) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Some(stem) = name.strip_suffix(".zip") else {
+                    continue;
+                };
+                if !entry.path().is_file() || store.index.contains_key(stem) {
+                    continue;
+                }
+                if is_hash(stem) {
+                    match hash_file(&entry.path()) {
+                        Ok(hash) if hash == stem => {
+                            store.index.insert(hash, ArchiveEntry::default());
+                            changed = true;
+                            continue;
+                        }
+                        Ok(_) => warning!("{name} does not match its hash"),
+                        Err(e) => {
+                            error!("Failed to read {name}: {e}");
+                            continue;
+                        }
+                    }
+                }
+                if !import {
+                    continue;
+                }
+                match store.insert(&entry.path(), &name, None, true) {
+                    Ok(_) => changed = true,
+                    Err(e) => error!("Failed to move {name} into the archive store: {e}"),
+                }
+            }
+        }
+        if changed {
+            store.save();
+        }
+        store
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path(&self, hash: &str) -> PathBuf {
+        self.root.join(format!("{hash}.zip"))
+    }
+
+    pub fn entry(&self, hash: &str) -> Option<&ArchiveEntry> {
+        self.index.get(hash)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<String> {
+        self.index
+            .iter()
+            .find(|(_, entry)| entry.names.iter().any(|n| n == name))
+            .map(|(hash, _)| hash.clone())
+    }
+
+    pub fn find_by_ids(&self, mod_id: u64, file_id: u64) -> Option<String> {
+        let source = ArchiveSource { mod_id, file_id };
+        self.index
+            .iter()
+            .find(|(_, entry)| entry.sources.contains(&source))
+            .map(|(hash, _)| hash.clone())
+    }
+
+    /// Copies a file into the store and returns its hash
+    pub fn add_file(
+        &mut self,
+        path: &Path,
+        name: &str,
+        mod_id: u64,
+        file_id: u64,
+    ) -> io::Result<String> {
+        self.add(path, name, Some(ArchiveSource { mod_id, file_id }), false)
+    }
+
+    /// Moves a finished download into the store and returns its hash
+    pub fn add_download(
+        &mut self,
+        path: &Path,
+        name: &str,
+        mod_id: u64,
+        file_id: u64,
+    ) -> io::Result<String> {
+        self.add(path, name, Some(ArchiveSource { mod_id, file_id }), true)
+    }
+
+    /// Moves an archive from a pack into the store, keeping the names and sources it had there.
+    /// The caller already checked that `hash` is the hash of the file.
+    pub fn add_entry(&mut self, path: &Path, hash: &str, entry: &ArchiveEntry) -> io::Result<()> {
+        let stored = self.path(hash);
+        if stored.exists() {
+            remove_file(path)?;
+        } else {
+            create_dir_all(&self.root)?;
+            move_file(path, &stored)?;
+        }
+        self.index.entry(hash.to_string()).or_default().merge(entry);
+        self.save();
+        Ok(())
+    }
+
+    pub fn add_source(&mut self, hash: &str, mod_id: u64, file_id: u64) {
+        if let Some(entry) = self.index.get_mut(hash) {
+            let source = ArchiveSource { mod_id, file_id };
+            if !entry.sources.contains(&source) {
+                entry.sources.push(source);
+                self.save();
+            }
+        }
+    }
+
+    /// Forgets that the archive came from `mod_id` and `file_id`, deleting it once nothing else
+    /// does
+    pub fn remove_source(&mut self, hash: &str, mod_id: u64, file_id: u64) -> io::Result<()> {
+        let Some(entry) = self.index.get_mut(hash) else {
+            return Ok(());
+        };
+        entry
+            .sources
+            .retain(|s| (s.mod_id, s.file_id) != (mod_id, file_id));
+        if entry.sources.is_empty() {
+            self.remove(hash)
+        } else {
+            self.save();
+            Ok(())
+        }
+    }
+
+    pub fn remove(&mut self, hash: &str) -> io::Result<()> {
+        self.index.remove(hash);
+        self.save();
+        match remove_file(self.path(hash)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Moves every stored archive and the index into `root`, putting them back if any move fails.
+    /// A store already in `root` is merged with this one, other files there are left alone.
+    pub fn relocate(&mut self, root: &Path) -> io::Result<()> {
+        if root == self.root {
+            return Ok(());
+        }
+        create_dir_all(root)?;
+        let mut target = ArchiveStore::open(root);
+        let mut moved: Vec<(PathBuf, PathBuf)> = vec![];
+        let mut duplicates: Vec<PathBuf> = vec![];
+        for hash in self.index.keys() {
+            let (from, to) = (self.path(hash), target.path(hash));
+            if !from.exists() {
+                continue;
+            }
+            // Same hash, same contents
+            if to.exists() {
+                duplicates.push(from);
+                continue;
+            }
+            if let Err(e) = move_file(&from, &to) {
+                for (from, to) in moved.iter().rev() {
+                    let _ = move_file(to, from);
+                }
+                return Err(e);
+            }
+            moved.push((from, to));
+        }
+        for (hash, entry) in &self.index {
+            target.index.entry(hash.clone()).or_default().merge(entry);
+        }
+        for path in duplicates {
+            let _ = remove_file(path);
+        }
+        let _ = remove_file(self.root.join(INDEX_FILE));
+        *self = target;
+        self.save();
+        Ok(())
+    }
+
+    fn add(
+        &mut self,
+        path: &Path,
+        name: &str,
+        source: Option<ArchiveSource>,
+        take: bool,
+    ) -> io::Result<String> {
+        let hash = self.insert(path, name, source, take)?;
+        self.save();
+        Ok(hash)
+    }
+
+    /// Stores the file and indexes it without saving the index
+    fn insert(
+        &mut self,
+        path: &Path,
+        name: &str,
+        source: Option<ArchiveSource>,
+        take: bool,
+    ) -> io::Result<String> {
+        let hash = hash_file(path)?;
+        let stored = self.path(&hash);
+        if stored.exists() {
+            // Never delete the stored copy itself
+            if take && path != stored {
+                remove_file(path)?;
+            }
+        } else {
+            create_dir_all(&self.root)?;
+            if take {
+                move_file(path, &stored)?;
+            } else {
+                copy(path, &stored)?;
+            }
+        }
+        let entry = self.index.entry(hash.clone()).or_default();
+        if !entry.names.iter().any(|n| n == name) {
+            entry.names.push(name.to_string());
+        }
+        if let Some(source) = source
+            && !entry.sources.contains(&source)
+        {
+            entry.sources.push(source);
+        }
+        Ok(hash)
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.index)
+            .map_err(io::Error::from)
+            .and_then(|json| {
+                let temp = self.root.join(format!("{INDEX_FILE}.tmp"));
+                File::create(&temp)?.write_all(json.as_bytes())?;
+                rename(temp, self.root.join(INDEX_FILE))
+            });
+        if let Err(e) = result {
+            error!("Failed to save archive index: {e}");
+        }
+    }
+}
+
+/// Renames a file, copying it instead when the destination is on another drive
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    rename(from, to).or_else(|_| {
+        copy(from, to)?;
+        remove_file(from)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    fn stored_files(root: &Path) -> Vec<String> {
+        let mut names: Vec<String> = read_dir(root)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|n| n.ends_with(".zip"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn add_file_copies_and_indexes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let source = dir.join("Mod.zip");
+        write(&source, b"mod").unwrap();
+        let root = dir.join("store");
+        let mut store = ArchiveStore::open(&root);
+        let hash = store.add_file(&source, "Mod.zip", 1, 2).unwrap();
+        assert!(is_hash(&hash));
+        assert!(source.exists());
+        assert!(store.path(&hash).exists());
+        assert_eq!(store.find_by_name("Mod.zip"), Some(hash.clone()));
+        assert_eq!(store.find_by_ids(1, 2), Some(hash.clone()));
+
+        let store = ArchiveStore::open(&root);
+        assert_eq!(store.entry(&hash).unwrap().names, vec!["Mod.zip"]);
+    }
+
+    #[test]
+    fn identical_archives_are_stored_once() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let root = dir.join("store");
+        let mut store = ArchiveStore::open(&root);
+        for name in ["A.zip", "B.zip"] {
+            write(dir.join(name), b"same").unwrap();
+            store.add_download(&dir.join(name), name, 1, 1).unwrap();
+            assert!(!dir.join(name).exists());
+        }
+        assert_eq!(stored_files(&root).len(), 1);
+        let hash = store.find_by_name("B.zip").unwrap();
+        assert_eq!(store.entry(&hash).unwrap().names, vec!["A.zip", "B.zip"]);
+    }
+
+    #[test]
+    fn missing_index_keeps_stored_archives() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join("Mod.zip"), b"mod").unwrap();
+        let hash = ArchiveStore::open_or_import(dir)
+            .find_by_name("Mod.zip")
+            .unwrap();
+        remove_file(dir.join(INDEX_FILE)).unwrap();
+
+        let store = ArchiveStore::open(dir);
+        assert!(store.path(&hash).exists());
+        assert!(store.entry(&hash).is_some());
+        assert_eq!(stored_files(dir), vec![format!("{hash}.zip")]);
+    }
+
+    #[test]
+    fn corrupt_index_keeps_stored_archives() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join("Mod.zip"), b"mod").unwrap();
+        let hash = ArchiveStore::open_or_import(dir)
+            .find_by_name("Mod.zip")
+            .unwrap();
+        write(dir.join(INDEX_FILE), "{").unwrap();
+
+        let store = ArchiveStore::open(dir);
+        assert!(store.path(&hash).exists());
+        assert!(store.entry(&hash).is_some());
+    }
+
+    #[test]
+    fn first_open_only_takes_in_zips() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join("Loose.zip"), b"loose").unwrap();
+        write(dir.join("index.json.bak"), b"{}").unwrap();
+        write(dir.join("notes.txt"), b"notes").unwrap();
+
+        let store = ArchiveStore::open_or_import(dir);
+        let hash = store.find_by_name("Loose.zip").unwrap();
+        assert_eq!(stored_files(dir), vec![format!("{hash}.zip")]);
+        assert!(dir.join("index.json.bak").exists());
+        assert!(dir.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn loose_zips_are_left_once_there_is_an_index() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join("Mod.zip"), b"mod").unwrap();
+        ArchiveStore::open_or_import(dir);
+        write(dir.join("Later.zip"), b"later").unwrap();
+
+        for store in [ArchiveStore::open(dir), ArchiveStore::open_or_import(dir)] {
+            assert!(store.find_by_name("Later.zip").is_none());
+        }
+        assert!(dir.join("Later.zip").exists());
+    }
+
+    #[test]
+    fn remove_deletes_archive() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join("Mod.zip"), b"mod").unwrap();
+        let mut store = ArchiveStore::open_or_import(dir);
+        let hash = store.find_by_name("Mod.zip").unwrap();
+        store.remove(&hash).unwrap();
+        assert!(store.entry(&hash).is_none());
+        assert!(!store.path(&hash).exists());
+        assert!(ArchiveStore::open(dir).entry(&hash).is_none());
+    }
+
+    #[test]
+    fn remove_source_keeps_archive_with_other_sources() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(dir.join("A.zip"), b"same").unwrap();
+        write(dir.join("B.zip"), b"same").unwrap();
+        let mut store = ArchiveStore::open(dir);
+        let hash = store.add_file(&dir.join("A.zip"), "A.zip", 1, 1).unwrap();
+        store.add_file(&dir.join("B.zip"), "B.zip", 1, 2).unwrap();
+
+        store.remove_source(&hash, 1, 2).unwrap();
+        assert!(store.path(&hash).exists());
+        assert_eq!(store.find_by_ids(1, 1), Some(hash.clone()));
+        assert_eq!(store.find_by_ids(1, 2), None);
+
+        store.remove_source(&hash, 1, 1).unwrap();
+        assert!(store.entry(&hash).is_none());
+        assert!(!store.path(&hash).exists());
+    }
+
+    #[test]
+    fn relocate_moves_archives_and_index() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (old, new) = (dir.join("old"), dir.join("new"));
+        create_dir_all(&old).unwrap();
+        write(old.join("Mod.zip"), b"mod").unwrap();
+        let mut store = ArchiveStore::open_or_import(&old);
+        let hash = store.find_by_name("Mod.zip").unwrap();
+        store.relocate(&new).unwrap();
+        assert_eq!(store.root(), new);
+        assert!(stored_files(&old).is_empty());
+        assert!(!old.join(INDEX_FILE).exists());
+
+        let store = ArchiveStore::open(&new);
+        assert_eq!(store.find_by_name("Mod.zip"), Some(hash));
+    }
+
+    #[test]
+    fn relocate_merges_existing_store() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (old, new) = (dir.join("old"), dir.join("new"));
+        create_dir_all(&old).unwrap();
+        create_dir_all(&new).unwrap();
+        write(old.join("Mine.zip"), b"mine").unwrap();
+        write(old.join("Shared.zip"), b"shared").unwrap();
+        write(new.join("Theirs.zip"), b"theirs").unwrap();
+        write(new.join("Copy.zip"), b"shared").unwrap();
+        ArchiveStore::open_or_import(&new);
+
+        let mut store = ArchiveStore::open_or_import(&old);
+        store.relocate(&new).unwrap();
+        assert!(stored_files(&old).is_empty());
+        assert_eq!(stored_files(&new).len(), 3);
+        let store = ArchiveStore::open(&new);
+        for name in ["Mine.zip", "Theirs.zip"] {
+            assert!(store.find_by_name(name).is_some());
+        }
+        let shared = store.find_by_name("Shared.zip").unwrap();
+        assert_eq!(
+            store.entry(&shared).unwrap().names,
+            vec!["Copy.zip", "Shared.zip"]
+        );
+    }
+
+    #[test]
+    fn relocate_leaves_other_files_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (old, new) = (dir.join("old"), dir.join("new"));
+        create_dir_all(&old).unwrap();
+        create_dir_all(&new).unwrap();
+        write(old.join("Mod.zip"), b"mod").unwrap();
+        write(new.join("Unrelated.zip"), b"unrelated").unwrap();
+
+        let mut store = ArchiveStore::open_or_import(&old);
+        store.relocate(&new).unwrap();
+        assert!(new.join("Unrelated.zip").exists());
+        assert!(store.find_by_name("Unrelated.zip").is_none());
+        let store = ArchiveStore::open_or_import(&new);
+        assert!(store.find_by_name("Unrelated.zip").is_none());
+        assert!(store.find_by_name("Mod.zip").is_some());
+    }
+}